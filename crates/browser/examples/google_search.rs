@@ -1,5 +1,5 @@
 use browser::{BrowserWorker, TimeoutConfig};
-use rocky_core::{Job, Action, BrowserAction, ScrapingAction, JobWorker, BrowserConfig, BrowserType};
+use rocky_core::{Job, Action, BrowserAction, ScrapingAction, JobWorker, BrowserConfig, BrowserType, ImageFormat, WaitCriteria};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -18,6 +18,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 selector: "textarea[name='q'], input[name='q']".to_string(),
                 text: "rust web scraping".to_string(),
                 clear_first: true,
+                wait: WaitCriteria::default(),
             }),
             
             // Press Enter
@@ -28,25 +29,37 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             
             // Take screenshot first to see what we got
             Action::Browser(BrowserAction::Screenshot {
-                path: "/tmp/google_search_after_nav.png".to_string(),
+                path: Some("/tmp/google_search_after_nav.png".to_string()),
                 full_page: true,
+                as_base64: false,
+                format: ImageFormat::Png,
+                quality: None,
             }),
             
             // Wait for search results container
             Action::Browser(BrowserAction::WaitFor {
                 selector: "#search".to_string(),
                 timeout_ms: 20000,
+                pierce_shadow: false,
             }),
             
             // Extract all text from search results
             Action::Scraping(ScrapingAction::Extract {
                 selector: "#search h3".to_string(),
                 attr: None,
+                limit: None,
+                offset: None,
+                resolve_urls: false,
+                pierce_shadow: false,
+                store_as: None,
             }),
             
             Action::Browser(BrowserAction::Screenshot {
-                path: "/tmp/google_search_results.png".to_string(),
+                path: Some("/tmp/google_search_results.png".to_string()),
                 full_page: true,
+                as_base64: false,
+                format: ImageFormat::Png,
+                quality: None,
             }),
         ],
         browser_config: Some(BrowserConfig {
@@ -55,7 +68,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             viewport_width: Some(1920),
             viewport_height: Some(1080),
             fail_on_captcha: true, // Enable CAPTCHA detection
+            ..Default::default()
         }),
+        tags: Vec::new(),
+        proxy: None,
+        referer: None,
+        priority: 0,
+        timeout_ms: None,
+        collect_partial: false,
     };
     
     println!("🔍 Starting Google search...\n");
@@ -70,29 +90,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("\n=== Search Results ===");
     
     for (key, value) in result.output.as_object().unwrap() {
-        if key.starts_with("extract") {
-            if let Some(arr) = value.as_array() {
-                let count = arr.len();
-                if count > 0 {
-                    println!("\n📊 {}: {} items", key, count);
-                    for (i, item) in arr.iter().take(5).enumerate() {
-                        match item {
-                            serde_json::Value::String(s) => {
-                                if !s.trim().is_empty() {
-                                    println!("  {}. {}", i + 1, s.chars().take(100).collect::<String>());
-                                }
+        if key.starts_with("extract")
+            && let Some(arr) = value.as_array()
+        {
+            let count = arr.len();
+            if count > 0 {
+                println!("\n📊 {}: {} items", key, count);
+                for (i, item) in arr.iter().take(5).enumerate() {
+                    match item {
+                        serde_json::Value::String(s)
+                            if !s.trim().is_empty() => {
+                                println!("  {}. {}", i + 1, s.chars().take(100).collect::<String>());
                             }
-                            serde_json::Value::Object(obj) => {
-                                if let Some(text) = obj.get("text") {
-                                    if let Some(s) = text.as_str() {
-                                        if !s.trim().is_empty() {
-                                            println!("  {}. {}", i + 1, s.chars().take(100).collect::<String>());
-                                        }
-                                    }
-                                }
+                        serde_json::Value::Object(obj) => {
+                            if let Some(text) = obj.get("text")
+                                && let Some(s) = text.as_str()
+                                && !s.trim().is_empty()
+                            {
+                                println!("  {}. {}", i + 1, s.chars().take(100).collect::<String>());
                             }
-                            _ => {}
                         }
+                        _ => {}
                     }
                 }
             }
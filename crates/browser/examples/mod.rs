@@ -1,11 +1,11 @@
 use browser::BrowserWorker;
 use rocky_core::{
-    Action, BrowserAction, BrowserConfig, BrowserType, Job, ScrapingAction, ScrollTarget,
+    Action, BrowserAction, BrowserConfig, BrowserType, ImageFormat, Job, ScrapingAction,
+    ScrollTarget, WaitCriteria,
 };
 use rocky_parser::ParserWorker;
 use rocky_scheduler::Scheduler;
 use rocky_storage::JsonFileStorage;
-use tokio::time::{Duration, sleep};
 
 #[tokio::main]
 async fn main() {
@@ -27,13 +27,29 @@ async fn main() {
                 Action::Scraping(ScrapingAction::Extract {
                     selector: "p".to_string(),
                     attr: None,
+                    limit: None,
+                    offset: None,
+                    resolve_urls: false,
+                    pierce_shadow: false,
+                    store_as: None,
                 }),
                 Action::Scraping(ScrapingAction::ExtractMultiple {
                     selector: "a".to_string(),
                     attrs: vec!["href".to_string(), "text".to_string()],
+                    limit: None,
+                    offset: None,
+                    resolve_urls: false,
+                    pierce_shadow: false,
+                    stream: None,
                 }),
             ],
             browser_config: None,
+            tags: Vec::new(),
+            proxy: None,
+            referer: None,
+            priority: 0,
+            timeout_ms: None,
+            collect_partial: false,
         },
         // Browser automation job with interactions
         Job {
@@ -47,6 +63,7 @@ async fn main() {
                 Action::Scraping(ScrapingAction::WaitFor {
                     selector: "h1".to_string(),
                     timeout_ms: 5000,
+                    pierce_shadow: false,
                 }),
                 Action::Browser(BrowserAction::Scroll {
                     target: ScrollTarget::Bottom,
@@ -54,10 +71,19 @@ async fn main() {
                 Action::Scraping(ScrapingAction::Extract {
                     selector: "p".to_string(),
                     attr: None,
+                    limit: None,
+                    offset: None,
+                    resolve_urls: false,
+                    pierce_shadow: false,
+                    store_as: None,
                 }),
                 Action::Browser(BrowserAction::Screenshot {
-                    path: "results/job-002-screenshot.png".to_string(),
+                    // Full-page captures are large, so keep this one as a compressed JPEG
+                    path: Some("results/job-002-screenshot.jpg".to_string()),
                     full_page: true,
+                    as_base64: false,
+                    format: ImageFormat::Jpeg,
+                    quality: Some(80),
                 }),
             ],
             browser_config: Some(BrowserConfig {
@@ -66,7 +92,14 @@ async fn main() {
                 viewport_width: Some(1920),
                 viewport_height: Some(1080),
                 fail_on_captcha: true,
+                ..Default::default()
             }),
+            tags: Vec::new(),
+            proxy: None,
+            referer: None,
+            priority: 0,
+            timeout_ms: None,
+            collect_partial: false,
         },
         Job {
             id: "job-003".to_string(),
@@ -79,12 +112,14 @@ async fn main() {
                 Action::Browser(BrowserAction::WaitAndClick {
                     selector: "textarea[name='q']".to_string(),
                     timeout_ms: 5000,
+                    wait: WaitCriteria::default(),
                 }),
                 // Type the search query
                 Action::Browser(BrowserAction::Type {
                     selector: "textarea[name='q']".to_string(),
                     text: "Rust programming language".to_string(),
                     clear_first: true,
+                    wait: WaitCriteria::default(),
                 }),
                 // Submit the search
                 Action::Browser(BrowserAction::PressKey {
@@ -96,16 +131,27 @@ async fn main() {
                 Action::Scraping(ScrapingAction::WaitFor {
                     selector: "#search".to_string(),
                     timeout_ms: 5000,
+                    pierce_shadow: false,
                 }),
                 // Extract all h3 elements (search result titles)
                 Action::Scraping(ScrapingAction::Extract {
                     selector: "h3".to_string(),
                     attr: None,
+                    limit: None,
+                    offset: None,
+                    resolve_urls: false,
+                    pierce_shadow: false,
+                    store_as: None,
                 }),
                 // Also extract h3s with their parent link URLs
                 Action::Scraping(ScrapingAction::ExtractMultiple {
                     selector: "h3".to_string(),
                     attrs: vec!["text".to_string()],
+                    limit: None,
+                    offset: None,
+                    resolve_urls: false,
+                    pierce_shadow: false,
+                    stream: None,
                 }),
                 // Scroll to see more results
                 Action::Browser(BrowserAction::Scroll {
@@ -113,8 +159,11 @@ async fn main() {
                 }),
                 // Take a screenshot of the results
                 Action::Browser(BrowserAction::Screenshot {
-                    path: "results/job-003-screenshot.png".to_string(),
+                    path: Some("results/job-003-screenshot.png".to_string()),
                     full_page: false,
+                    as_base64: false,
+                    format: ImageFormat::Png,
+                    quality: None,
                 }),
             ],
             browser_config: Some(BrowserConfig {
@@ -123,14 +172,20 @@ async fn main() {
                 viewport_width: Some(1280),
                 viewport_height: Some(720),
                 fail_on_captcha: true,
+                ..Default::default()
             }),
+            tags: Vec::new(),
+            proxy: None,
+            referer: None,
+            priority: 0,
+            timeout_ms: None,
+            collect_partial: false,
         },
     ];
 
     for job in jobs {
-        scheduler_handle.submit(job).unwrap();
-        sleep(Duration::from_millis(200)).await;
+        scheduler_handle.submit_async(job).await.unwrap();
     }
 
-    sleep(Duration::from_secs(10)).await;
+    scheduler_handle.wait_idle().await;
 }
@@ -1,5 +1,6 @@
 pub mod worker;
 pub mod shared;
 
-pub use worker::BrowserWorker;
-pub use shared::TimeoutConfig;
\ No newline at end of file
+pub use worker::{BrowserWorker, FirefoxWorker, ActionMiddleware, ActionContext, Next, TimingMiddleware, ScreenshotOnErrorMiddleware};
+pub use worker::{BrowserPage, PageError, ScriptResult, ScreenshotOptions, ScreenshotArea, Clip};
+pub use shared::{TimeoutConfig, BrowserPoolConfig};
\ No newline at end of file
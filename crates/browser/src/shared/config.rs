@@ -1,3 +1,4 @@
+use rocky_core::TimeoutOverrides;
 use std::time::Duration;
 
 #[derive(Debug, Clone)]
@@ -34,6 +35,24 @@ impl TimeoutConfig {
         self
     }
 
+    /// Replace any timeouts set in a job's overrides, keeping the rest
+    pub fn with_overrides(mut self, overrides: &TimeoutOverrides) -> Self {
+        let fields = [
+            (&mut self.element_wait, overrides.element_wait_ms),
+            (&mut self.navigation, overrides.navigation_ms),
+            (&mut self.page_stable, overrides.page_stable_ms),
+            (&mut self.cookie_banner, overrides.cookie_banner_ms),
+            (&mut self.check_interval, overrides.check_interval_ms),
+            (&mut self.settle_delay, overrides.settle_delay_ms),
+        ];
+        for (field, ms) in fields {
+            if let Some(ms) = ms {
+                *field = Duration::from_millis(ms);
+            }
+        }
+        self
+    }
+
     pub fn fast() -> Self {
         Self {
             element_wait: Duration::from_millis(8000),
@@ -55,4 +74,22 @@ impl TimeoutConfig {
             settle_delay: Duration::from_millis(2000),
         }
     }
-}
\ No newline at end of file
+}
+
+/// Limits for the browsers a worker keeps running between jobs
+#[derive(Debug, Clone)]
+pub struct BrowserPoolConfig {
+    /// Most browser processes alive at once
+    pub max_browsers: usize,
+    /// Most jobs sharing one browser at once, each in its own tab and browser context
+    pub max_pages_per_browser: usize,
+}
+
+impl Default for BrowserPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_browsers: 2,
+            max_pages_per_browser: 4,
+        }
+    }
+}
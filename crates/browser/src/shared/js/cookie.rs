@@ -18,10 +18,20 @@ pub const FIND_AND_CLICK_COOKIE: &str = r#"
         for (const pattern of patterns) {
             if (text.toLowerCase().includes(pattern.toLowerCase())) {
                 btn.click();
-                return { clicked: true, text };
+                return { clicked: true, text, pattern, bannerPresent: true };
             }
         }
     }
-    return { clicked: false };
+    
+    // Report whether something banner-like is showing even though no button matched
+    const bannerPresent = Array.from(document.querySelectorAll(
+        '[class*="cookie"], [id*="cookie"], [class*="consent"], [id*="consent"]'
+    )).some(el => {
+        const rect = el.getBoundingClientRect();
+        const style = window.getComputedStyle(el);
+        return rect.width > 0 && rect.height > 0 && 
+               style.visibility !== 'hidden' && style.display !== 'none';
+    });
+    return { clicked: false, bannerPresent };
 }
 "#;
\ No newline at end of file
@@ -15,7 +15,9 @@ pub const CHECK_ELEMENT_STATE: &str = r#"
         
         const centerX = rect.left + rect.width / 2;
         const centerY = rect.top + rect.height / 2;
-        const topEl = document.elementFromPoint(centerX, centerY);
+        // Hit-test in the element's own tree so shadow content isn't reported as obscured by its host
+        const root = el.getRootNode();
+        const topEl = (root.elementFromPoint ? root : document).elementFromPoint(centerX, centerY);
         const isObscured = topEl && !el.contains(topEl) && topEl !== el;
         
         return {
@@ -33,6 +35,8 @@ pub const CHECK_ELEMENT_STATE: &str = r#"
                 width: rect.width, 
                 height: rect.height 
             },
+            scroll: { x: window.scrollX, y: window.scrollY },
+            devicePixelRatio: window.devicePixelRatio || 1,
             matchedSelector: selector,
             actualTag: el.tagName.toLowerCase()
         };
@@ -87,10 +91,28 @@ pub const SAFE_CLICK: &str = r#"
 }
 "#;
 
-pub const EXTRACT_TEXT: &str = r#"
+/// Every element matching `selector` in document order, descending into open shadow roots
+/// Closed roots are unreachable from script and skipped; selectors can't cross a root boundary
+pub const QUERY_ALL_DEEP: &str = r#"
 (selector) => {
+    const results = [];
+    const visit = (root) => {
+        const walker = document.createTreeWalker(root, NodeFilter.SHOW_ELEMENT);
+        for (let node = walker.nextNode(); node; node = walker.nextNode()) {
+            if (node.matches(selector)) results.push(node);
+            if (node.shadowRoot) visit(node.shadowRoot);
+        }
+    };
+    visit(document);
+    return results;
+}
+"#;
+
+pub const EXTRACT_TEXT: &str = r#"
+(selector, offset = 0, limit = null) => {
     try {
         return Array.from(document.querySelectorAll(selector))
+            .slice(offset || 0, limit == null ? undefined : (offset || 0) + limit)
             .map(e => e.textContent?.trim() || '');
     } catch (error) {
         return [];
@@ -99,20 +121,127 @@ pub const EXTRACT_TEXT: &str = r#"
 "#;
 
 pub const EXTRACT_ATTR: &str = r#"
-(selector, attr) => {
+(selector, attr, offset = 0, limit = null, resolveUrls = false) => {
     try {
+        const resolve = (value) => {
+            if (!resolveUrls || (attr !== 'href' && attr !== 'src')) return value;
+            if (!value || /^\s*javascript:/i.test(value)) return value;
+            try { return new URL(value, document.baseURI).href; } catch (error) { return value; }
+        };
         return Array.from(document.querySelectorAll(selector))
-            .map(e => e.getAttribute(attr) || '');
+            .slice(offset || 0, limit == null ? undefined : (offset || 0) + limit)
+            .map(e => resolve(e.getAttribute(attr) || ''));
     } catch (error) {
         return [];
     }
 }
 "#;
 
+pub const EXTRACT_FIRST: &str = r#"
+(selectors, attr = null) => {
+    for (const selector of selectors) {
+        const elements = Array.from(document.querySelectorAll(selector));
+        if (elements.length > 0) {
+            return {
+                selector,
+                values: elements.map(e => attr ? (e.getAttribute(attr) || '') : (e.textContent?.trim() || ''))
+            };
+        }
+    }
+    return { selector: null, values: [] };
+}
+"#;
+
+pub const EXTRACT_INLINE_STATE: &str = r#"
+(varName, path = null) => {
+    let value = window[varName];
+    if (path) {
+        for (const key of path.split('.')) {
+            if (value == null) break;
+            value = value[key];
+        }
+    }
+    // Round-trip through JSON to drop functions and break cycles into plain data
+    return value === undefined ? null : JSON.parse(JSON.stringify(value));
+}
+"#;
+
+pub const EXTRACT_TABLE: &str = r#"
+(selector, header = false) => {
+    const table = document.querySelector(selector);
+    if (!table) return [];
+    // Rows directly in the table or its sections, so nested tables are left out
+    const rows = [];
+    for (const child of table.children) {
+        const tag = child.tagName.toLowerCase();
+        if (tag === 'thead' || tag === 'tbody' || tag === 'tfoot') {
+            for (const row of child.children) {
+                if (row.tagName.toLowerCase() === 'tr') rows.push(row);
+            }
+        } else if (tag === 'tr') {
+            rows.push(child);
+        }
+    }
+    const cells = rows.map(row => {
+        const values = [];
+        for (const cell of row.children) {
+            const tag = cell.tagName.toLowerCase();
+            if (tag !== 'td' && tag !== 'th') continue;
+            const span = Math.min(Math.max(parseInt(cell.getAttribute('colspan'), 10) || 1, 1), 1000);
+            const text = cell.textContent?.trim() || '';
+            for (let i = 0; i < span; i++) values.push(text);
+        }
+        return values;
+    });
+
+    const columns = [];
+    if (header && cells.length > 0) {
+        cells.shift().forEach((name, i) => {
+            const base = name === '' ? String(i) : name;
+            let unique = base;
+            let n = 2;
+            while (columns.includes(unique)) {
+                unique = `${base}_${n}`;
+                n++;
+            }
+            columns.push(unique);
+        });
+    }
+
+    return cells.map(values => {
+        const width = Math.max(values.length, columns.length);
+        const obj = {};
+        for (let i = 0; i < width; i++) {
+            obj[i < columns.length ? columns[i] : String(i)] = values[i] ?? '';
+        }
+        return obj;
+    });
+}
+"#;
+
+pub const JSON_LD_BLOCKS: &str = r#"
+() => Array.from(document.querySelectorAll('script[type="application/ld+json"]'))
+    .map(s => s.textContent || '')
+"#;
+
+pub const EXTRACT_DATA_ATTRS: &str = r#"
+(selector) => {
+    return Array.from(document.querySelectorAll(selector))
+        .map(e => ({ ...e.dataset, text: e.textContent?.trim() || '' }));
+}
+"#;
+
 pub const EXTRACT_MULTIPLE: &str = r#"
-(selector, attrs) => {
+(selector, attrs, offset = 0, limit = null, resolveUrls = false) => {
     try {
-        return Array.from(document.querySelectorAll(selector)).map(e => {
+        const resolve = (attr, value) => {
+            if (!resolveUrls || (attr !== 'href' && attr !== 'src')) return value;
+            if (!value || /^\s*javascript:/i.test(value)) return value;
+            try { return new URL(value, document.baseURI).href; } catch (error) { return value; }
+        };
+        const elements = Array.from(document.querySelectorAll(selector))
+            .slice(offset || 0, limit == null ? undefined : (offset || 0) + limit);
+        return elements.map(e => {
             const result = {};
             attrs.forEach(attr => {
                 if (attr === 'text') {
@@ -120,7 +249,7 @@ pub const EXTRACT_MULTIPLE: &str = r#"
                 } else if (attr === 'html') {
                     result[attr] = e.innerHTML || '';
                 } else {
-                    result[attr] = e.getAttribute(attr) || '';
+                    result[attr] = resolve(attr, e.getAttribute(attr) || '');
                 }
             });
             return result;
@@ -131,6 +260,103 @@ pub const EXTRACT_MULTIPLE: &str = r#"
 }
 "#;
 
+pub const EXTRACT_ITEMS: &str = r#"
+(container, fields, offset = 0, limit = null) => {
+    try {
+        const readValue = (el, attr) => {
+            if (!attr || attr === 'text') return el.textContent?.trim() || '';
+            if (attr === 'html') return el.innerHTML || '';
+            return el.getAttribute(attr) || '';
+        };
+        
+        const resolveAxis = (item, axis) => {
+            if (!axis) return item;
+            if (axis === 'Parent') return item.parentElement;
+            if (axis === 'NextSibling') return item.nextElementSibling;
+            if (axis === 'PreviousSibling') return item.previousElementSibling;
+            if (axis.Closest) return item.closest(axis.Closest);
+            return null;
+        };
+        
+        return Array.from(document.querySelectorAll(container))
+            .slice(offset || 0, limit == null ? undefined : (offset || 0) + limit)
+            .map(item => {
+                const record = {};
+                fields.forEach(field => {
+                    const base = resolveAxis(item, field.axis);
+                    const matches = !base ? []
+                        : field.selector
+                            ? Array.from(base.querySelectorAll(field.selector))
+                            : [base];
+                    if (field.multiple) {
+                        record[field.name] = matches.map(el => readValue(el, field.attr));
+                    } else {
+                        record[field.name] = matches.length > 0 ? readValue(matches[0], field.attr) : null;
+                    }
+                });
+                return record;
+            });
+    } catch (error) {
+        return [];
+    }
+}
+"#;
+
+pub const EXTRACT_META: &str = r#"
+() => {
+    const attrOf = (selector, attr) => {
+        const el = document.querySelector(selector);
+        return el ? (el.getAttribute(attr) || '') : null;
+    };
+    const tags = (prefix) => {
+        const result = {};
+        document.querySelectorAll('meta[property], meta[name]').forEach(el => {
+            const key = el.getAttribute('property') || el.getAttribute('name') || '';
+            if (key.startsWith(prefix)) {
+                result[key] = el.getAttribute('content') || '';
+            }
+        });
+        return result;
+    };
+    const titleEl = document.querySelector('title');
+    
+    return {
+        title: titleEl ? titleEl.textContent.trim() : null,
+        description: attrOf('meta[name="description"]', 'content'),
+        canonical: attrOf('link[rel="canonical"]', 'href'),
+        open_graph: tags('og:'),
+        twitter: tags('twitter:')
+    };
+}
+"#;
+
+pub const EXTRACT_ANCHORS: &str = r#"
+(selector) => {
+    return Array.from(document.querySelectorAll(selector))
+        .filter(el => el.hasAttribute('href'))
+        .map(el => {
+            let hrefAbsolute = null;
+            try {
+                hrefAbsolute = new URL(el.getAttribute('href'), document.baseURI).href;
+            } catch (e) {}
+            return {
+                href_absolute: hrefAbsolute,
+                text: el.textContent.trim(),
+                rel: el.getAttribute('rel'),
+                target: el.getAttribute('target')
+            };
+        });
+}
+"#;
+
+pub const GET_VIEWPORT: &str = r#"
+() => ({
+    width: window.innerWidth,
+    height: window.innerHeight,
+    devicePixelRatio: window.devicePixelRatio
+})
+"#;
+
 pub const TYPE_TEXT: &str = r#"
 (selector, text, clear = false) => {
     try {
@@ -215,6 +441,26 @@ pub const SUBMIT_FORM: &str = r#"
 }
 "#;
 
+pub const HIGHLIGHT_ELEMENT: &str = r#"
+(selector) => {
+    const el = document.querySelector(selector);
+    if (!el) return false;
+    const style = document.createElement('style');
+    style.id = 'rocky-highlight-style';
+    style.textContent = '[data-rocky-highlight] { outline: 3px solid #ff0040 !important; outline-offset: 2px !important; }';
+    document.head.appendChild(style);
+    el.setAttribute('data-rocky-highlight', '');
+    return true;
+}
+"#;
+
+pub const CLEAR_HIGHLIGHT: &str = r#"
+() => {
+    document.getElementById('rocky-highlight-style')?.remove();
+    document.querySelectorAll('[data-rocky-highlight]').forEach(el => el.removeAttribute('data-rocky-highlight'));
+}
+"#;
+
 pub const HOVER_ELEMENT: &str = r#"
 (selector) => {
     try {
@@ -238,8 +484,25 @@ pub const HOVER_ELEMENT: &str = r#"
 }
 "#;
 
+pub const IS_FILE_INPUT: &str = r#"
+(selector) => {
+    const el = document.querySelector(selector);
+    return !!el && el.tagName === 'INPUT' && el.type === 'file';
+}
+"#;
+
+pub const PRESS_KEY: &str = r#"
+(key) => {
+    const el = document.activeElement || document.body;
+    ['keydown', 'keypress', 'keyup'].forEach(eventType => {
+        el.dispatchEvent(new KeyboardEvent(eventType, { key, bubbles: true, cancelable: true }));
+    });
+    return { success: true };
+}
+"#;
+
 pub const SELECT_OPTION: &str = r#"
-(selector, value) => {
+(selector, by) => {
     try {
         const el = document.querySelector(selector);
         if (!el) return { success: false, error: 'Element not found' };
@@ -248,46 +511,78 @@ pub const SELECT_OPTION: &str = r#"
             return { success: false, error: 'Element is not a select element' };
         }
         
-        // Try to find and select the option
-        const option = Array.from(el.options).find(opt => 
-            opt.value === value || opt.text === value
-        );
+        const options = Array.from(el.options);
+        const availableOptions = () => options.map(opt => ({
+            value: opt.value,
+            text: opt.text
+        }));
+        
+        let matched = [];
+        if ('Value' in by) {
+            matched = options.filter(opt => opt.value === by.Value).slice(0, 1);
+        } else if ('Text' in by) {
+            matched = options.filter(opt => opt.text.trim() === by.Text.trim()).slice(0, 1);
+        } else if ('Index' in by) {
+            matched = by.Index < options.length ? [options[by.Index]] : [];
+        } else if ('Multiple' in by) {
+            if (!el.multiple) {
+                return { success: false, error: 'Element does not allow multiple selection' };
+            }
+            matched = options.filter(opt => 
+                by.Multiple.includes(opt.value) || by.Multiple.includes(opt.text)
+            );
+        }
         
-        if (!option) {
+        if (matched.length === 0) {
             return { 
                 success: false, 
                 error: 'Option not found',
-                availableOptions: Array.from(el.options).map(opt => ({
-                    value: opt.value,
-                    text: opt.text
-                }))
+                availableOptions: availableOptions()
             };
         }
         
-        el.value = option.value;
+        if (el.multiple) {
+            options.forEach(opt => { opt.selected = false; });
+            matched.forEach(opt => { opt.selected = true; });
+        } else {
+            el.value = matched[0].value;
+        }
         el.dispatchEvent(new Event('change', { bubbles: true }));
         el.dispatchEvent(new Event('input', { bubbles: true }));
         
-        return { success: true, selectedValue: option.value, selectedText: option.text };
+        return { 
+            success: true, 
+            selected: matched.map(opt => ({ value: opt.value, text: opt.text }))
+        };
     } catch (error) {
         return { success: false, error: error.message };
     }
 }
 "#;
 
+pub const OVERRIDE_LOCALE: &str = r#"
+(languages) => {
+    Object.defineProperty(Navigator.prototype, 'language', { get: () => languages[0], configurable: true });
+    Object.defineProperty(Navigator.prototype, 'languages', { get: () => languages.slice(), configurable: true });
+}
+"#;
+
 pub const SET_COOKIE: &str = r#"
 (name, value, options = {}) => {
     try {
+        options = options || {};
+        // Attribute values can't be URI-encoded, so drop anything that would start another attribute
+        const attr = (v) => String(v).replace(/[;\r\n]/g, '');
         let cookieString = `${encodeURIComponent(name)}=${encodeURIComponent(value)}`;
         
-        if (options.domain) cookieString += `; domain=${options.domain}`;
-        if (options.path) cookieString += `; path=${options.path}`;
+        if (options.domain) cookieString += `; domain=${attr(options.domain)}`;
+        if (options.path) cookieString += `; path=${attr(options.path)}`;
         else cookieString += '; path=/';
         
-        if (options.maxAge) cookieString += `; max-age=${options.maxAge}`;
-        if (options.expires) cookieString += `; expires=${options.expires}`;
+        if (options.maxAge) cookieString += `; max-age=${attr(options.maxAge)}`;
+        if (options.expires) cookieString += `; expires=${attr(options.expires)}`;
         if (options.secure) cookieString += '; secure';
-        if (options.sameSite) cookieString += `; samesite=${options.sameSite}`;
+        if (options.sameSite) cookieString += `; samesite=${attr(options.sameSite)}`;
         
         document.cookie = cookieString;
         
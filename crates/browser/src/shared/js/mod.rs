@@ -4,10 +4,66 @@ pub mod cookie;
 
 use serde_json::Value;
 
+/// Render a value as a JavaScript literal that is safe to splice into a script
+///
+/// JSON already escapes quotes, backslashes and control characters; U+2028 and U+2029
+/// are escaped too since older engines treat them as line breaks inside string literals.
+/// Always pass selectors and user text through this (or `build_js_call`), never `format!`.
+pub fn js_literal(value: &Value) -> String {
+    value.to_string()
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029")
+}
+
+/// Call a JS function expression with the given arguments, each rendered with `js_literal`
 pub fn build_js_call(func: &str, args: &[Value]) -> String {
     let args_str = args.iter()
-        .map(|v| v.to_string())
+        .map(js_literal)
         .collect::<Vec<_>>()
         .join(", ");
     format!("({})({})", func, args_str)
-}
\ No newline at end of file
+}
+
+/// `build_js_call`, but when `pierce_shadow` is set the function's `document.querySelector`
+/// and `querySelectorAll` calls also match inside open shadow roots
+///
+/// The override is removed as soon as the call returns, so `func` must be synchronous.
+pub fn build_query_js_call(func: &str, args: &[Value], pierce_shadow: bool) -> String {
+    let call = build_js_call(func, args);
+    if !pierce_shadow {
+        return call;
+    }
+    format!(
+        "(() => {{ const queryAllDeep = ({}); \
+        document.querySelectorAll = (s) => queryAllDeep(s); \
+        document.querySelector = (s) => queryAllDeep(s)[0] || null; \
+        try {{ return {}; }} finally {{ delete document.querySelectorAll; delete document.querySelector; }} }})()",
+        element::QUERY_ALL_DEEP, call
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn js_literal_escapes_quotes_and_backslashes() {
+        assert_eq!(js_literal(&json!(r#"say "hi" to 'them'"#)), r#""say \"hi\" to 'them'""#);
+        assert_eq!(js_literal(&json!(r"C:\path\n")), r#""C:\\path\\n""#);
+        assert_eq!(js_literal(&json!("a\nb</script>")), r#""a\nb</script>""#);
+    }
+
+    #[test]
+    fn js_literal_keeps_unicode_but_escapes_line_separators() {
+        assert_eq!(js_literal(&json!("日本語 café 🎉")), "\"日本語 café 🎉\"");
+        assert_eq!(js_literal(&json!("a\u{2028}b\u{2029}c")), r#""a\u2028b\u2029c""#);
+        assert_eq!(js_literal(&json!({ "key\u{2028}": ["\u{2029}"] })), r#"{"key\u2028":["\u2029"]}"#);
+    }
+
+    #[test]
+    fn build_js_call_renders_every_argument_as_a_literal() {
+        let call = build_js_call("(s, n) => s.repeat(n)", &[json!("it's \"quoted\"\u{2028}"), json!(2)]);
+        assert_eq!(call, r#"((s, n) => s.repeat(n))("it's \"quoted\"\u2028", 2)"#);
+    }
+}
@@ -7,32 +7,67 @@ pub const CHECK_LOADING: &str = r#"
 "#;
 
 pub const WAIT_FOR_NETWORK_IDLE: &str = r#"
-() => {
+(idleMs = 500, timeoutMs = 5000) => {
     return new Promise((resolve) => {
-        let timeout;
-        let count = 0;
-        
+        const start = Date.now();
+        // Finished requests get a resource entry, so a growing count means traffic
+        const snapshot = () => {
+            const entries = performance.getEntriesByType('resource');
+            return { total: entries.length, active: entries.filter(r => !r.responseEnd).length };
+        };
+        let last = snapshot();
+        let quietSince = Date.now();
+
         const check = () => {
-            const active = performance.getEntriesByType('resource').filter(r => !r.responseEnd).length;
-            if (active === 0) {
-                count++;
-                if (count >= 3) { // 3 consecutive checks with no activity
-                    resolve(true);
-                    return;
-                }
+            const now = Date.now();
+            const current = snapshot();
+            if (current.active > 0 || current.total !== last.total) {
+                quietSince = now;
+            }
+            last = current;
+            if (now - quietSince >= idleMs) {
+                resolve({ idle: true, active: current.active, elapsed: now - start });
+            } else if (now - start >= timeoutMs) {
+                resolve({ idle: false, active: current.active, elapsed: now - start });
             } else {
-                count = 0;
+                setTimeout(check, 100);
             }
-            timeout = setTimeout(check, 200);
         };
-        
+
         check();
-        
-        // Failsafe timeout
-        setTimeout(() => {
-            clearTimeout(timeout);
-            resolve(false);
-        }, 5000);
     });
 }
-"#;
\ No newline at end of file
+"#;
+
+pub const MATCH_URL: &str = r#"
+(pattern) => {
+    const href = window.location.href;
+    let re;
+    const lastSlash = pattern.lastIndexOf('/');
+    if (pattern.startsWith('/') && lastSlash > 0) {
+        // /regex/flags
+        re = new RegExp(pattern.slice(1, lastSlash), pattern.slice(lastSlash + 1));
+    } else {
+        // Glob: * matches any run of characters, ? matches a single character
+        const escaped = pattern.replace(/[.+^${}()|[\]\\]/g, '\\$&')
+            .replace(/\*/g, '.*')
+            .replace(/\?/g, '.');
+        re = new RegExp('^' + escaped + '$');
+    }
+    return { href, matches: re.test(href) };
+}
+"#;
+
+pub const COUNT_ELEMENTS: &str = r#"
+(selector) => document.querySelectorAll(selector).length
+"#;
+
+pub const PRINT_READY: &str = r#"
+async () => {
+    if (document.fonts) {
+        await document.fonts.ready;
+    }
+    const pendingImages = Array.from(document.images).filter(img => !img.complete).length;
+    return { readyState: document.readyState, pendingImages };
+}
+"#;
@@ -2,5 +2,5 @@ pub mod js;
 pub mod errors;
 pub mod config;
 
-pub use config::TimeoutConfig;
+pub use config::{TimeoutConfig, BrowserPoolConfig};
 pub use errors::to_job_error;
\ No newline at end of file
@@ -0,0 +1,915 @@
+use base64::prelude::*;
+use chromiumoxide::cdp::browser_protocol::network::CookieParam;
+use rocky_core::{Action, JobError, ScrapingAction, BrowserAction, ScrollTarget, WaitCriteria, CookieBannerResult, RecordSink, StreamConfig};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+use crate::shared::{js, to_job_error, TimeoutConfig};
+use super::page::{BrowserPage, Clip, ScreenshotArea, ScreenshotOptions};
+use super::wait::WaitStrategy;
+
+pub struct ActionHandler {
+    wait_strategy: WaitStrategy,
+    fail_on_captcha: bool,
+    job_id: String,
+    record_sink: Option<Arc<dyn RecordSink>>,
+}
+
+impl ActionHandler {
+    pub fn new(config: TimeoutConfig, fail_on_captcha: bool) -> Self {
+        Self {
+            wait_strategy: WaitStrategy::new(config),
+            fail_on_captcha,
+            job_id: String::new(),
+            record_sink: None,
+        }
+    }
+
+    pub fn with_record_sink(mut self, job_id: &str, sink: Option<Arc<dyn RecordSink>>) -> Self {
+        self.job_id = job_id.to_string();
+        self.record_sink = sink;
+        self
+    }
+
+    /// Page through matches in batches, writing each batch to the record sink
+    /// `build_js` receives the batch offset and limit
+    async fn stream_records(
+        &self,
+        page: &dyn BrowserPage,
+        key: &str,
+        offset: Option<usize>,
+        limit: Option<usize>,
+        config: &StreamConfig,
+        build_js: impl Fn(usize, usize) -> String,
+    ) -> Result<Value, JobError> {
+        let Some(sink) = &self.record_sink else {
+            return Err(JobError::config_error("record_sink", "Streaming extract requires a record sink on the worker"));
+        };
+        let batch_size = config.batch_size.max(1);
+        let mut next_offset = offset.unwrap_or(0);
+        let mut streamed = 0;
+        
+        loop {
+            let remaining = limit.map_or(usize::MAX, |l| l - streamed);
+            if remaining == 0 {
+                break;
+            }
+            let batch_limit = batch_size.min(remaining);
+            let result = page.evaluate(build_js(next_offset, batch_limit)).await
+                .map_err(|e| JobError::script_error(format!("Streaming extract failed: {}", e)))?;
+            let records = result.value().and_then(|v| v.as_array()).cloned().unwrap_or_default();
+            if records.is_empty() {
+                break;
+            }
+            
+            sink.write_records(&self.job_id, key, &records).await?;
+            streamed += records.len();
+            next_offset += records.len();
+            debug!(streamed, key, "Streamed records");
+            
+            if records.len() < batch_limit {
+                break;
+            }
+            if config.delay_ms > 0 {
+                sleep(Duration::from_millis(config.delay_ms)).await;
+            }
+        }
+        
+        Ok(json!({ "streamed": streamed, "next_offset": next_offset }))
+    }
+
+    /// Fail with a CAPTCHA error if `fail_on_captcha` is set and the page shows one
+    pub async fn check_captcha(&self, page: &dyn BrowserPage) -> Result<(), JobError> {
+        if !self.fail_on_captcha {
+            return Ok(());
+        }
+        
+        let js = js::build_js_call(js::element::DETECT_CAPTCHA, &[]);
+        let result = page.evaluate(js).await
+            .map_err(|e| JobError::script_error(format!("CAPTCHA detection failed: {}", e)))?;
+        
+        if let Some(value) = result.value()
+            && let Some(obj) = value.as_object()
+        {
+            // Log detection details for debugging
+            let url = obj.get("url").and_then(|v| v.as_str()).unwrap_or("unknown");
+            let detected = obj.get("detected").and_then(|v| v.as_bool()).unwrap_or(false);
+                
+            debug!(url, "Checked page for CAPTCHA");
+                
+            if detected {
+                let types = obj.get("types")
+                    .and_then(|v| v.as_array())
+                    .map(|arr| arr.iter()
+                        .filter_map(|v| v.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "))
+                    .unwrap_or_else(|| "unknown".to_string());
+                    
+                let keywords = obj.get("keywords")
+                    .and_then(|v| v.as_array())
+                    .map(|arr| arr.iter()
+                        .filter_map(|v| v.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "))
+                    .unwrap_or_default();
+                    
+                let page_title = obj.get("pageTitle")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("unknown");
+                    
+                let url = obj.get("url")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("unknown");
+                    
+                let title_match = obj.get("titleMatch").and_then(|v| v.as_bool()).unwrap_or(false);
+                let url_match = obj.get("urlMatch").and_then(|v| v.as_bool()).unwrap_or(false);
+                    
+                let body_sample = obj.get("bodyTextSample")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                    
+                let message = if !keywords.is_empty() {
+                    format!("CAPTCHA or consent page detected on '{}'", page_title)
+                } else if !types.is_empty() {
+                    format!("CAPTCHA detected on '{}' (type: {})", page_title, types)
+                } else {
+                    format!("CAPTCHA or verification page detected on '{}'", page_title)
+                };
+                    
+                return Err(JobError::captcha_detected(message)
+                    .with_context(json!({
+                        "types": types,
+                        "keywords": keywords,
+                        "page_title": page_title,
+                        "url": url,
+                        "title_match": title_match,
+                        "url_match": url_match,
+                        "body_sample": body_sample
+                    })));
+            }
+        }
+        
+        Ok(())
+    }
+
+    async fn wait_for(&self, page: &dyn BrowserPage, selector: &str, timeout_ms: u64, pierce_shadow: bool) -> Result<(), JobError> {
+        if pierce_shadow {
+            self.wait_strategy.wait_for_shadow_element(page, selector, timeout_ms).await
+        } else {
+            self.wait_strategy.wait_for_element(page, selector, timeout_ms, false).await
+        }
+    }
+
+    async fn scroll_to_element(&self, page: &dyn BrowserPage, selector: &str) -> Result<(), JobError> {
+        let js = js::build_js_call(js::element::SCROLL_INTO_VIEW, &[json!(selector), json!("center")]);
+        page.evaluate(js).await
+            .map_err(|e| to_job_error(e, "Scroll"))?;
+        sleep(Duration::from_millis(300)).await;
+        Ok(())
+    }
+
+    async fn scroll(&self, page: &dyn BrowserPage, target: &ScrollTarget) -> Result<(), JobError> {
+        let js = match target {
+            ScrollTarget::Element { selector } => {
+                js::build_js_call(js::element::SCROLL_INTO_VIEW, &[json!(selector), json!("center")])
+            }
+            ScrollTarget::Position { x, y } => format!("window.scrollTo({},{})", x, y),
+            ScrollTarget::Bottom => "window.scrollTo(0,document.body.scrollHeight)".to_string(),
+            ScrollTarget::Top => "window.scrollTo(0,0)".to_string(),
+        };
+        
+        page.evaluate(js).await
+            .map_err(|e| to_job_error(e, "Scroll"))?;
+        sleep(Duration::from_millis(500)).await;
+        Ok(())
+    }
+
+    async fn scroll_height(&self, page: &dyn BrowserPage) -> Result<f64, JobError> {
+        let height = page.evaluate("document.body.scrollHeight").await
+            .map_err(|e| to_job_error(e, "ScrollUntilStable"))?;
+        Ok(height.value().and_then(|v| v.as_f64()).unwrap_or(0.0))
+    }
+
+    /// Set the cookies saved at `path`, returning how many were set; a missing file sets none
+    async fn load_cookies(&self, page: &dyn BrowserPage, path: &str) -> Result<usize, JobError> {
+        let data = match tokio::fs::read(path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                warn!(job_id = %self.job_id, path = %path, "No saved cookies, continuing without them");
+                return Ok(0);
+            }
+            Err(e) => return Err(JobError::browser_error(format!("Failed to read cookies: {}", e))),
+        };
+        let saved: Vec<Value> = serde_json::from_slice(&data)
+            .map_err(|e| JobError::config_error("path", format!("LoadCookies failed: {} is not a cookie file: {}", path, e)))?;
+
+        let cookies = saved.into_iter()
+            .map(|mut cookie| {
+                // Session cookies are saved with `expires: -1`, which would set them already expired
+                if (cookie.get("session").and_then(|v| v.as_bool()).unwrap_or(false)
+                    || cookie.get("expires").and_then(|v| v.as_f64()).is_some_and(|t| t < 0.0))
+                    && let Some(cookie) = cookie.as_object_mut()
+                {
+                    cookie.remove("expires");
+                }
+                serde_json::from_value::<CookieParam>(cookie)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| JobError::config_error("path", format!("LoadCookies failed: invalid cookie in {}: {}", path, e)))?;
+
+        let count = page.set_cookies(cookies).await
+            .map_err(|e| JobError::browser_error(format!("LoadCookies failed: {}", e)))?;
+        debug!(job_id = %self.job_id, path = %path, count, "Loaded cookies");
+        Ok(count)
+    }
+
+    /// Whether any element matches `selector` right now
+    pub async fn is_present(&self, page: &dyn BrowserPage, selector: &str) -> Result<bool, JobError> {
+        let js = js::build_js_call(js::wait::COUNT_ELEMENTS, &[json!(selector)]);
+        let count = page.evaluate(js).await
+            .map_err(|e| to_job_error(e, "IfPresent"))?
+            .value().and_then(|v| v.as_u64()).unwrap_or(0);
+        Ok(count > 0)
+    }
+
+    /// Whether the page's tab has crashed, closed or detached, so no further actions can run
+    pub async fn page_closed(&self, page: &dyn BrowserPage) -> bool {
+        match tokio::time::timeout(Duration::from_secs(2), page.evaluate("true")).await {
+            Ok(Ok(_)) => false,
+            // Context loss just means a navigation is in progress
+            Ok(Err(e)) => !e.is_context_lost(),
+            // A page that is merely busy is not closed
+            Err(_) => false,
+        }
+    }
+
+    pub async fn handle(
+        &self,
+        action: &Action,
+        page: &dyn BrowserPage,
+        output: &mut Map<String, Value>,
+    ) -> Result<(), JobError> {
+        match action {
+            Action::Scraping(a) => self.handle_scraping(a, page, output).await,
+            Action::Browser(a) => self.handle_browser(a, page, output).await,
+            // The worker's executor handles these, recording the error under the job-level index
+            Action::Optional(_) => Err(JobError::config_error("actions", "Optional can only be used at the job level or inside IfPresent or another Optional")),
+        }
+    }
+
+    pub async fn handle_scraping(
+        &self,
+        action: &ScrapingAction,
+        page: &dyn BrowserPage,
+        output: &mut Map<String, Value>,
+    ) -> Result<(), JobError> {
+        match action {
+            ScrapingAction::Fetch { .. } => Ok(()),
+            ScrapingAction::Request { .. } => {
+                // Running it as a plain navigation would silently drop the method and body
+                Err(JobError::config_error("actions", "Request actions are only supported by ParserWorker"))
+            }
+            ScrapingAction::WaitFor { selector, timeout_ms, pierce_shadow } => {
+                self.wait_for(page, selector, *timeout_ms, *pierce_shadow).await?;
+                output.insert(format!("waitfor:{}", selector), json!(true));
+                Ok(())
+            }
+            ScrapingAction::AssertAbsent { selector } => {
+                let js = js::build_js_call(js::wait::COUNT_ELEMENTS, &[json!(selector)]);
+                let result = page.evaluate(js).await
+                    .map_err(|e| to_job_error(e, "AssertAbsent"))?;
+                let count = result.value().and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+                if count > 0 {
+                    return Err(JobError::unexpected_element(selector, count));
+                }
+                output.insert(format!("assert_absent:{}", selector), json!(true));
+                Ok(())
+            }
+            ScrapingAction::AssertCount { selector, min, max } => {
+                let js = js::build_js_call(js::wait::COUNT_ELEMENTS, &[json!(selector)]);
+                let result = page.evaluate(js).await
+                    .map_err(|e| to_job_error(e, "AssertCount"))?;
+                let count = result.value().and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+                output.insert(format!("count:{}", selector), json!(count));
+                JobError::check_count(selector, count, *min, *max)
+            }
+            ScrapingAction::Extract { selector, attr, limit, offset, resolve_urls, pierce_shadow, .. } => {
+                let js = if let Some(a) = attr {
+                    js::build_query_js_call(js::element::EXTRACT_ATTR, &[json!(selector), json!(a), json!(offset), json!(limit), json!(resolve_urls)], *pierce_shadow)
+                } else {
+                    js::build_query_js_call(js::element::EXTRACT_TEXT, &[json!(selector), json!(offset), json!(limit)], *pierce_shadow)
+                };
+                
+                let result = page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("Extract failed: {}", e)))?;
+                
+                output.insert(format!("extract:{}", selector), result.value().cloned().unwrap_or(json!([])));
+                Ok(())
+            }
+            ScrapingAction::ExtractOne { selector, attr, default, resolve_urls, pierce_shadow, .. } => {
+                // The same scripts as `Extract`, limited to the first match
+                let js = if let Some(a) = attr {
+                    js::build_query_js_call(js::element::EXTRACT_ATTR, &[json!(selector), json!(a), json!(0), json!(1), json!(resolve_urls)], *pierce_shadow)
+                } else {
+                    js::build_query_js_call(js::element::EXTRACT_TEXT, &[json!(selector), json!(0), json!(1)], *pierce_shadow)
+                };
+
+                let result = page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("ExtractOne failed: {}", e)))?;
+                let value = result.value().and_then(|v| v.get(0)).cloned().filter(|v| !v.is_null());
+
+                output.insert(format!("extract_one:{}", selector), value.unwrap_or_else(|| json!(default)));
+                Ok(())
+            }
+            ScrapingAction::ExtractInlineState { var_name, path } => {
+                let js = js::build_js_call(js::element::EXTRACT_INLINE_STATE, &[json!(var_name), json!(path)]);
+                let result = page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("ExtractInlineState failed: {}", e)))?;
+
+                output.insert(format!("inline_state:{}", var_name), result.value().cloned().unwrap_or(json!(null)));
+                Ok(())
+            }
+            ScrapingAction::ExtractTable { selector, header } => {
+                let js = js::build_js_call(js::element::EXTRACT_TABLE, &[json!(selector), json!(header)]);
+                let result = page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("ExtractTable failed: {}", e)))?;
+
+                output.insert(format!("extract_table:{}", selector), result.value().cloned().unwrap_or(json!([])));
+                Ok(())
+            }
+            ScrapingAction::ExtractStructuredData { types } => {
+                let js = js::build_js_call(js::element::JSON_LD_BLOCKS, &[]);
+                let result = page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("ExtractStructuredData failed: {}", e)))?;
+                let blocks: Vec<String> = result.value()
+                    .and_then(|v| serde_json::from_value(v.clone()).ok())
+                    .unwrap_or_default();
+
+                output.insert("structured_data".to_string(), json!(rocky_parser::structured_data(&blocks, types)));
+                Ok(())
+            }
+            ScrapingAction::ExtractDataAttrs { selector } => {
+                let js = js::build_js_call(js::element::EXTRACT_DATA_ATTRS, &[json!(selector)]);
+                let result = page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("ExtractDataAttrs failed: {}", e)))?;
+
+                output.insert(format!("data_attrs:{}", selector), result.value().cloned().unwrap_or(json!([])));
+                Ok(())
+            }
+            ScrapingAction::ExtractFirst { selectors, attr, .. } => {
+                let js = js::build_js_call(js::element::EXTRACT_FIRST, &[json!(selectors), json!(attr)]);
+                let result = page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("ExtractFirst failed: {}", e)))?;
+
+                output.insert(
+                    format!("extract_first:{}", selectors.join(", ")),
+                    result.value().cloned().unwrap_or(json!({ "selector": null, "values": [] })),
+                );
+                Ok(())
+            }
+            ScrapingAction::ExtractMultiple { selector, attrs, limit, offset, resolve_urls, pierce_shadow, stream: Some(config) } => {
+                let key = format!("extract_multiple:{}", selector);
+                let summary = self.stream_records(page, &key, *offset, *limit, config, |o, l| {
+                    js::build_query_js_call(js::element::EXTRACT_MULTIPLE, &[json!(selector), json!(attrs), json!(o), json!(l), json!(resolve_urls)], *pierce_shadow)
+                }).await?;
+                output.insert(key, summary);
+                Ok(())
+            }
+            ScrapingAction::ExtractMultiple { selector, attrs, limit, offset, resolve_urls, pierce_shadow, stream: None } => {
+                let js = js::build_query_js_call(js::element::EXTRACT_MULTIPLE, &[json!(selector), json!(attrs), json!(offset), json!(limit), json!(resolve_urls)], *pierce_shadow);
+                let result = page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("ExtractMultiple failed: {}", e)))?;
+                
+                output.insert(format!("extract_multiple:{}", selector), result.value().cloned().unwrap_or(json!([])));
+                Ok(())
+            }
+            ScrapingAction::ExtractMeta => {
+                let js = js::build_js_call(js::element::EXTRACT_META, &[]);
+                let result = page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("ExtractMeta failed: {}", e)))?;
+                
+                output.insert("meta".to_string(), result.value().cloned().unwrap_or(json!({})));
+                Ok(())
+            }
+            ScrapingAction::ExtractAnchors { selector } => {
+                let selector = selector.as_deref().unwrap_or("a[href]");
+                let js = js::build_js_call(js::element::EXTRACT_ANCHORS, &[json!(selector)]);
+                let result = page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("ExtractAnchors failed: {}", e)))?;
+
+                output.insert("anchors".to_string(), result.value().cloned().unwrap_or(json!([])));
+                Ok(())
+            }
+            ScrapingAction::ExtractItems { container, fields, limit, offset, stream: Some(config) } => {
+                let key = format!("extract_items:{}", container);
+                let summary = self.stream_records(page, &key, *offset, *limit, config, |o, l| {
+                    js::build_js_call(js::element::EXTRACT_ITEMS, &[json!(container), json!(fields), json!(o), json!(l)])
+                }).await?;
+                output.insert(key, summary);
+                Ok(())
+            }
+            ScrapingAction::ExtractItems { container, fields, limit, offset, stream: None } => {
+                let js = js::build_js_call(js::element::EXTRACT_ITEMS, &[json!(container), json!(fields), json!(offset), json!(limit)]);
+                let result = page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("ExtractItems failed: {}", e)))?;
+                
+                output.insert(format!("extract_items:{}", container), result.value().cloned().unwrap_or(json!([])));
+                Ok(())
+            }
+        }
+    }
+    
+    pub async fn handle_browser(
+        &self,
+        action: &BrowserAction,
+        page: &dyn BrowserPage,
+        output: &mut Map<String, Value>,
+    ) -> Result<(), JobError> {
+        match action {
+            BrowserAction::Click { selector, timeout_ms, wait } => {
+                let criteria = WaitCriteria { require_enabled: Some(wait.require_enabled.unwrap_or(true)), ..wait.clone() };
+                self.wait_strategy.wait_for_element_with(page, selector, *timeout_ms, &criteria).await?;
+                self.scroll_to_element(page, selector).await?;
+                
+                let js = js::build_js_call(js::element::SAFE_CLICK, &[json!(selector)]);
+                page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("Click failed: {}", e)))?;
+                
+                sleep(Duration::from_millis(300)).await;
+                output.insert(format!("click:{}", selector), json!(true));
+                Ok(())
+            }
+            BrowserAction::Paginate { next_selector, extract, max_pages, stop_when_missing } => {
+                let mut pages: Vec<Value> = Vec::new();
+                loop {
+                    let mut page_output = Map::new();
+                    Box::pin(self.handle(extract, page, &mut page_output)).await?;
+                    // Actions write one key each, so keep just the value when there is one
+                    let extracted = if page_output.len() == 1 {
+                        page_output.into_iter().next().map(|(_, v)| v).unwrap_or(Value::Null)
+                    } else {
+                        Value::Object(page_output)
+                    };
+
+                    // A next button that never goes away but no longer changes the page would loop forever
+                    if pages.last() == Some(&extracted) {
+                        info!(page = pages.len() + 1, "Page extracted the same output as the last one, stopping");
+                        break;
+                    }
+                    pages.push(extracted);
+                    if pages.len() >= *max_pages as usize {
+                        break;
+                    }
+
+                    let js = js::build_js_call(js::wait::COUNT_ELEMENTS, &[json!(next_selector)]);
+                    let count = page.evaluate(js).await
+                        .map_err(|e| to_job_error(e, "Paginate"))?
+                        .value().and_then(|v| v.as_u64()).unwrap_or(0);
+                    if count == 0 {
+                        if *stop_when_missing {
+                            break;
+                        }
+                        return Err(JobError::element_not_found(next_selector));
+                    }
+
+                    self.scroll_to_element(page, next_selector).await?;
+                    let js = js::build_js_call(js::element::SAFE_CLICK, &[json!(next_selector)]);
+                    page.evaluate(js).await
+                        .map_err(|e| JobError::script_error(format!("Paginate click failed: {}", e)))?;
+                    self.wait_strategy.wait_for_stable(page, self.wait_strategy.page_stable_ms()).await?;
+                    debug!(page = pages.len() + 1, "Moved to next page");
+                }
+
+                output.insert(format!("paginate:{}", next_selector), json!(pages));
+                Ok(())
+            }
+            BrowserAction::IfPresent { .. } => {
+                // The worker's executor runs the branches so they go through middleware like any action
+                Err(JobError::config_error("actions", "IfPresent can only be used at the job level or inside IfPresent or Optional"))
+            }
+            BrowserAction::Type { selector, text, clear_first, wait } => {
+                self.wait_strategy.wait_for_element_with(page, selector, self.wait_strategy.element_wait_ms(), wait).await?;
+                
+                let js = js::build_js_call(js::element::TYPE_TEXT, &[json!(selector), json!(text), json!(clear_first)]);
+                page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("Type failed: {}", e)))?;
+                
+                sleep(Duration::from_millis(200)).await;
+                output.insert(format!("type:{}", selector), json!(text));
+                Ok(())
+            }
+            BrowserAction::PressKey { key } => {
+                // Special handling for Enter key - try to submit the active element's form
+                if key.to_lowercase() == "enter" {
+                    let js = r#"
+                        (() => {
+                            const el = document.activeElement;
+                            if (!el) return { success: false, error: 'No active element' };
+                            
+                            // Try form submission first
+                            const form = el.closest('form');
+                            if (form) {
+                                form.submit();
+                                return { success: true, method: 'form.submit' };
+                            }
+                            
+                            // Dispatch proper keyboard events
+                            const enterDown = new KeyboardEvent('keydown', {
+                                key: 'Enter',
+                                code: 'Enter',
+                                keyCode: 13,
+                                which: 13,
+                                bubbles: true,
+                                cancelable: true
+                            });
+                            el.dispatchEvent(enterDown);
+                            
+                            const enterPress = new KeyboardEvent('keypress', {
+                                key: 'Enter',
+                                code: 'Enter',
+                                keyCode: 13,
+                                which: 13,
+                                bubbles: true,
+                                cancelable: true
+                            });
+                            el.dispatchEvent(enterPress);
+                            
+                            const enterUp = new KeyboardEvent('keyup', {
+                                key: 'Enter',
+                                code: 'Enter',
+                                keyCode: 13,
+                                which: 13,
+                                bubbles: true,
+                                cancelable: true
+                            });
+                            el.dispatchEvent(enterUp);
+                            
+                            return { success: true, method: 'keyboard_events' };
+                        })()
+                    "#;
+                    
+                    page.evaluate(js).await
+                        .map_err(|e| JobError::script_error(format!("PressKey (Enter) failed: {}", e)))?;
+                } else {
+                    // Generic key press for other keys
+                    let js = js::build_js_call(js::element::PRESS_KEY, &[json!(key)]);
+
+                    page.evaluate(js).await
+                        .map_err(|e| JobError::script_error(format!("PressKey failed: {}", e)))?;
+                }
+                
+                sleep(Duration::from_millis(500)).await;
+                output.insert("press_key".to_string(), json!(key));
+                Ok(())
+            }
+            BrowserAction::Scroll { target } => {
+                self.scroll(page, target).await?;
+                output.insert("scroll".to_string(), json!(true));
+                Ok(())
+            }
+            BrowserAction::ScrollUntilStable { max_scrolls, pause_ms } => {
+                let mut height = self.scroll_height(page).await?;
+                let mut scrolls = 0;
+                while scrolls < *max_scrolls {
+                    page.evaluate("window.scrollTo(0,document.body.scrollHeight)").await
+                        .map_err(|e| to_job_error(e, "ScrollUntilStable"))?;
+                    scrolls += 1;
+                    sleep(Duration::from_millis(*pause_ms)).await;
+
+                    let new_height = self.scroll_height(page).await?;
+                    let grew = new_height > height;
+                    height = new_height;
+                    if !grew {
+                        break;
+                    }
+                }
+                output.insert("scroll_until_stable".to_string(), json!({ "scrolls": scrolls, "height": height }));
+                Ok(())
+            }
+            BrowserAction::Screenshot { path, full_page, as_base64, format, quality } => {
+                if path.is_none() && !as_base64 {
+                    return Err(JobError::config_error("path", "Screenshot needs a path or as_base64"));
+                }
+                if let Some(quality) = quality.filter(|q| *q > 100) {
+                    return Err(JobError::config_error("quality", format!("Screenshot quality {} is not between 0 and 100", quality))
+                        .with_context(json!({ "config_field": "quality", "quality": quality })));
+                }
+                let options = ScreenshotOptions {
+                    format: *format,
+                    quality: *quality,
+                    area: if *full_page { ScreenshotArea::FullPage } else { ScreenshotArea::Viewport },
+                };
+                let bytes = page.screenshot(&options).await
+                    .map_err(|e| JobError::browser_error(format!("Screenshot failed: {}", e)))?;
+
+                if let Some(path) = path {
+                    tokio::fs::write(path, &bytes).await
+                        .map_err(|e| JobError::browser_error(format!("Failed to save screenshot: {}", e)))?;
+                    output.insert("screenshot".to_string(), json!(path));
+                }
+                if *as_base64 {
+                    output.insert("screenshot_b64".to_string(), json!(BASE64_STANDARD.encode(&bytes)));
+                }
+                Ok(())
+            }
+            BrowserAction::WaitForNetworkIdle { timeout_ms, idle_ms } => {
+                let js = js::build_js_call(js::wait::WAIT_FOR_NETWORK_IDLE, &[json!(idle_ms), json!(timeout_ms)]);
+                let result = page.evaluate(js).await
+                    .map_err(|e| to_job_error(e, "WaitForNetworkIdle"))?;
+                let state = result.value().cloned().unwrap_or(json!({}));
+                if !state.get("idle").and_then(|v| v.as_bool()).unwrap_or(false) {
+                    return Err(JobError::timeout_error(format!("Network not idle for {}ms within {}ms", idle_ms, timeout_ms))
+                        .with_context(json!({
+                            "timeout_ms": timeout_ms,
+                            "idle_ms": idle_ms,
+                            "active_requests": state.get("active"),
+                        })));
+                }
+
+                output.insert("network_idle".to_string(), state.get("elapsed").cloned().unwrap_or(json!(null)));
+                Ok(())
+            }
+            BrowserAction::PrintToPdf { path, landscape, print_background } => {
+                self.wait_strategy.wait_for_stable(page, self.wait_strategy.page_stable_ms()).await?;
+
+                let js = js::build_js_call(js::wait::PRINT_READY, &[]);
+                let state = page.evaluate(js).await
+                    .map_err(|e| to_job_error(e, "PrintToPdf"))?;
+                let state = state.value().cloned().unwrap_or(json!({}));
+                let ready_state = state.get("readyState").and_then(|v| v.as_str()).unwrap_or("unknown");
+                if ready_state != "complete" {
+                    return Err(JobError::browser_error(format!("PrintToPdf failed: page is still loading ({})", ready_state))
+                        .with_context(json!({ "ready_state": ready_state, "pending_images": state.get("pendingImages") })));
+                }
+
+                let bytes = page.pdf(*landscape, *print_background).await
+                    .map_err(|e| JobError::browser_error(format!("PrintToPdf failed: {}", e)))?;
+
+                tokio::fs::write(path, &bytes).await
+                    .map_err(|e| JobError::browser_error(format!("Failed to save PDF: {}", e)))?;
+
+                output.insert("pdf".to_string(), json!(path));
+                Ok(())
+            }
+            BrowserAction::ScreenshotElement { selector, path, timeout_ms } => {
+                self.wait_strategy.wait_for_element(page, selector, *timeout_ms, false).await?;
+                self.scroll_to_element(page, selector).await?;
+                
+                // Measure after scrolling so the rect reflects the element's final position
+                let js = js::build_js_call(js::element::CHECK_ELEMENT_STATE, &[json!(selector)]);
+                let state = page.evaluate(js).await
+                    .map_err(|e| to_job_error(e, "ScreenshotElement"))?;
+                let state = state.value().cloned().unwrap_or(json!({}));
+                let num = |v: &Value, key: &str| v.get(key).and_then(|n| n.as_f64()).unwrap_or(0.0);
+                let rect = state.get("rect").cloned().unwrap_or(json!({}));
+                let scroll = state.get("scroll").cloned().unwrap_or(json!({}));
+                if num(&rect, "width") <= 0.0 || num(&rect, "height") <= 0.0 {
+                    let mut e = JobError::element_not_found(selector);
+                    e.add_context("exists", state.get("exists").cloned().unwrap_or(json!(false)));
+                    e.add_context("rect", rect);
+                    return Err(e);
+                }
+                
+                // Widen the clip to whole device pixels, so on HiDPI viewports fractional CSS
+                // coordinates don't cut off the element's edges
+                let dpr = state.get("devicePixelRatio").and_then(|v| v.as_f64()).filter(|d| *d > 0.0).unwrap_or(1.0);
+                let left = num(&rect, "left") + num(&scroll, "x");
+                let top = num(&rect, "top") + num(&scroll, "y");
+                let x = (left * dpr).floor() / dpr;
+                let y = (top * dpr).floor() / dpr;
+                let clip = Clip {
+                    x,
+                    y,
+                    width: ((left + num(&rect, "width")) * dpr).ceil() / dpr - x,
+                    height: ((top + num(&rect, "height")) * dpr).ceil() / dpr - y,
+                };
+                
+                let options = ScreenshotOptions {
+                    area: ScreenshotArea::Element { selector: selector.clone(), clip },
+                    ..ScreenshotOptions::default()
+                };
+                let bytes = page.screenshot(&options).await
+                    .map_err(|e| JobError::browser_error(format!("ScreenshotElement failed: {}", e)))?;
+                
+                tokio::fs::write(path, &bytes).await
+                    .map_err(|e| JobError::browser_error(format!("Failed to save screenshot: {}", e)))?;
+                
+                output.insert(format!("screenshot_element:{}", selector), json!({ "path": path, "rect": rect, "device_pixel_ratio": dpr }));
+                Ok(())
+            }
+            BrowserAction::Hover { selector } => {
+                self.wait_strategy.wait_for_element(page, selector, self.wait_strategy.element_wait_ms(), false).await?;
+                
+                let js = js::build_js_call(js::element::HOVER_ELEMENT, &[json!(selector)]);
+                page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("Hover failed: {}", e)))?;
+                
+                output.insert(format!("hover:{}", selector), json!(true));
+                Ok(())
+            }
+            BrowserAction::Select { selector, by } => {
+                self.wait_strategy.wait_for_element(page, selector, self.wait_strategy.element_wait_ms(), false).await?;
+                
+                let js = js::build_js_call(js::element::SELECT_OPTION, &[json!(selector), json!(by)]);
+                let result = page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("Select failed: {}", e)))?;
+                
+                let value = result.value().cloned().unwrap_or(json!({}));
+                if value.get("success").and_then(|v| v.as_bool()) != Some(true) {
+                    let error = value.get("error").and_then(|v| v.as_str()).unwrap_or("unknown error");
+                    return Err(JobError::script_error(format!("Select failed: {}", error))
+                        .with_context(json!({
+                            "selector": selector,
+                            "available_options": value.get("availableOptions").cloned().unwrap_or(json!([]))
+                        })));
+                }
+                
+                output.insert(format!("select:{}", selector), value.get("selected").cloned().unwrap_or(json!([])));
+                Ok(())
+            }
+            BrowserAction::UploadFile { selector, path } => {
+                // Browsers need an absolute path, and a missing file would otherwise upload nothing silently
+                let file = tokio::fs::canonicalize(path).await
+                    .ok()
+                    .filter(|p| p.is_file())
+                    .ok_or_else(|| JobError::browser_error(format!("UploadFile failed: no file at {}", path)))?;
+
+                self.wait_strategy.wait_for_element(page, selector, self.wait_strategy.element_wait_ms(), false).await?;
+                let js = js::build_js_call(js::element::IS_FILE_INPUT, &[json!(selector)]);
+                let is_file_input = page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("UploadFile failed: {}", e)))?
+                    .value() == Some(&json!(true));
+                if !is_file_input {
+                    return Err(JobError::browser_error("UploadFile failed: element is not a file input")
+                        .with_context(json!({ "selector": selector })));
+                }
+
+                page.set_input_files(selector, vec![file.to_string_lossy().into_owned()]).await
+                    .map_err(|e| JobError::browser_error(format!("UploadFile failed: {}", e)))?;
+
+                let filename = file.file_name().map(|n| n.to_string_lossy().into_owned());
+                output.insert(format!("upload:{}", selector), json!(filename));
+                Ok(())
+            }
+            BrowserAction::SetCookie { name, value, domain, reload_after } => {
+                let js = js::build_js_call(js::element::SET_COOKIE, &[json!(name), json!(value), json!({ "domain": domain })]);
+                let result = page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("SetCookie failed: {}", e)))?;
+                let result = result.value().cloned().unwrap_or(json!({}));
+                if !result.get("success").and_then(|v| v.as_bool()).unwrap_or(false) {
+                    let reason = result.get("error").and_then(|v| v.as_str()).unwrap_or("unknown error");
+                    return Err(JobError::script_error(format!("SetCookie failed: {}", reason))
+                        .with_context(json!({ "cookie": name })));
+                }
+
+                if *reload_after {
+                    page.reload().await
+                        .map_err(|e| JobError::navigation_error(format!("Reload after SetCookie failed: {}", e)))?;
+                    self.wait_strategy.wait_for_stable(page, self.wait_strategy.page_stable_ms()).await?;
+                }
+                
+                output.insert(format!("set_cookie:{}", name), json!(value));
+                Ok(())
+            }
+            BrowserAction::SaveCookies { path } => {
+                let cookies = page.cookies().await
+                    .map_err(|e| JobError::browser_error(format!("SaveCookies failed: {}", e)))?;
+                let json = serde_json::to_vec_pretty(&cookies)
+                    .map_err(|e| JobError::browser_error(format!("SaveCookies failed: {}", e)))?;
+                tokio::fs::write(path, json).await
+                    .map_err(|e| JobError::browser_error(format!("Failed to save cookies: {}", e)))?;
+
+                debug!(job_id = %self.job_id, path = %path, count = cookies.len(), "Saved cookies");
+                output.insert(format!("save_cookies:{}", path), json!(cookies.len()));
+                Ok(())
+            }
+            BrowserAction::LoadCookies { path } => {
+                let count = self.load_cookies(page, path).await?;
+                output.insert(format!("load_cookies:{}", path), json!(count));
+                Ok(())
+            }
+            BrowserAction::ExecuteScript { script, name } => {
+                let result = page.evaluate(script.clone()).await
+                    .map_err(|e| JobError::script_error(format!("ExecuteScript failed: {}", e)))?;
+                let value = result.value().cloned().unwrap_or(json!(null));
+
+                match name {
+                    Some(name) => {
+                        output.insert(format!("script:{}", name), value);
+                    }
+                    None => {
+                        let results = output.entry("execute_script").or_insert_with(|| json!([]));
+                        if let Some(results) = results.as_array_mut() {
+                            results.push(value);
+                        }
+                    }
+                }
+                Ok(())
+            }
+            BrowserAction::Navigate { url } => {
+                page.goto(url).await
+                    .map_err(|e| JobError::navigation_error(format!("Navigate failed: {}", e)))?;
+                self.wait_strategy.wait_for_stable(page, self.wait_strategy.page_stable_ms()).await?;
+                
+                // Check for CAPTCHA after navigation
+                if self.fail_on_captcha {
+                    debug!("Checking for CAPTCHA");
+                    self.check_captcha(page).await?;
+                    debug!("No CAPTCHA detected");
+                }
+                
+                output.insert("navigate".to_string(), json!(url));
+                Ok(())
+            }
+            BrowserAction::WaitForNavigation { timeout_ms } => {
+                self.wait_strategy.wait_for_navigation(page, *timeout_ms).await?;
+                
+                // Check for CAPTCHA after navigation completes
+                if self.fail_on_captcha {
+                    debug!("Checking for CAPTCHA");
+                    self.check_captcha(page).await?;
+                    debug!("No CAPTCHA detected");
+                }
+                
+                output.insert("wait_for_navigation".to_string(), json!(true));
+                Ok(())
+            }
+            BrowserAction::IsInViewport { selector } => {
+                let in_viewport = self.wait_strategy.is_in_viewport(page, selector).await?;
+                output.insert(format!("in_viewport:{}", selector), json!(in_viewport));
+                Ok(())
+            }
+            BrowserAction::WaitForUrl { pattern, timeout_ms } => {
+                let url = self.wait_strategy.wait_for_url(page, pattern, *timeout_ms).await?;
+                output.insert("wait_for_url".to_string(), json!(url));
+                Ok(())
+            }
+            BrowserAction::WaitForStableCount { selector, stable_checks, timeout_ms } => {
+                let count = self.wait_strategy.wait_for_stable_count(page, selector, *stable_checks, *timeout_ms).await?;
+                output.insert(format!("stable_count:{}", selector), json!(count));
+                Ok(())
+            }
+            BrowserAction::WaitFor { selector, timeout_ms, pierce_shadow } => {
+                self.wait_for(page, selector, *timeout_ms, *pierce_shadow).await?;
+                output.insert(format!("waitfor:{}", selector), json!(true));
+                Ok(())
+            }
+            BrowserAction::WaitAndClick { selector, timeout_ms, wait } => {
+                let criteria = WaitCriteria { require_enabled: Some(wait.require_enabled.unwrap_or(true)), ..wait.clone() };
+                self.wait_strategy.wait_for_element_with(page, selector, *timeout_ms, &criteria).await?;
+                self.scroll_to_element(page, selector).await?;
+                
+                let js = js::build_js_call(js::element::SAFE_CLICK, &[json!(selector)]);
+                page.evaluate(js).await
+                    .map_err(|e| JobError::script_error(format!("WaitAndClick failed: {}", e)))?;
+                
+                sleep(Duration::from_millis(300)).await;
+                output.insert(format!("wait_and_click:{}", selector), json!(true));
+                Ok(())
+            }
+            BrowserAction::HandleCookieBanner { timeout_ms } => {
+                let patterns = js::cookie::COOKIE_PATTERNS;
+                let js = js::build_js_call(js::cookie::FIND_AND_CLICK_COOKIE, &[json!(patterns)]);
+                
+                let start = std::time::Instant::now();
+                let timeout = Duration::from_millis(*timeout_ms);
+                let mut banner_present = false;
+                
+                while start.elapsed() < timeout {
+                    if let Ok(result) = page.evaluate(js.clone()).await
+                        && let Some(val) = result.value()
+                        && let Some(obj) = val.as_object()
+                    {
+                        banner_present |= obj.get("bannerPresent").and_then(|v| v.as_bool()).unwrap_or(false);
+                        if obj.get("clicked").and_then(|v| v.as_bool()) == Some(true) {
+                            let banner = CookieBannerResult {
+                                clicked: true,
+                                button_text: obj.get("text").and_then(|v| v.as_str()).map(String::from),
+                                matched_pattern: obj.get("pattern").and_then(|v| v.as_str()).map(String::from),
+                                banner_present: true,
+                                elapsed_ms: start.elapsed().as_millis() as u64,
+                            };
+                            output.insert("cookie_banner_handled".to_string(), json!(banner));
+                            sleep(Duration::from_millis(1000)).await; // Wait for banner to disappear
+                            return Ok(());
+                        }
+                    }
+                    sleep(Duration::from_millis(500)).await;
+                }
+                
+                let banner = CookieBannerResult {
+                    clicked: false,
+                    button_text: None,
+                    matched_pattern: None,
+                    banner_present,
+                    elapsed_ms: start.elapsed().as_millis() as u64,
+                };
+                output.insert("cookie_banner_handled".to_string(), json!(banner));
+                Ok(())
+            }
+        }
+    }
+}
\ No newline at end of file
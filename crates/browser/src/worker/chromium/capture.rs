@@ -0,0 +1,168 @@
+use chromiumoxide::cdp::browser_protocol::network::{
+    EventLoadingFailed, EventLoadingFinished, EventRequestWillBeSent, EventResponseReceived,
+};
+use chromiumoxide::cdp::js_protocol::runtime::EventConsoleApiCalled;
+use chromiumoxide::page::Page;
+use futures::StreamExt;
+use rocky_core::JobError;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+/// Most console entries kept per job; older ones are dropped first
+const MAX_CONSOLE_ENTRIES: usize = 1000;
+
+/// Most network entries kept per job, finished or still in flight
+const MAX_NETWORK_ENTRIES: usize = 5000;
+
+/// Entries recorded from page events by a background task, keeping only the newest `limit`
+struct Buffer {
+    entries: VecDeque<Value>,
+    /// Entries still being filled in, by CDP request id
+    pending: HashMap<String, Value>,
+    dropped: usize,
+    limit: usize,
+}
+
+impl Buffer {
+    fn new(limit: usize) -> Self {
+        Self { entries: VecDeque::new(), pending: HashMap::new(), dropped: 0, limit }
+    }
+
+    fn start(&mut self, id: &str, entry: Value) {
+        if self.pending.len() >= self.limit {
+            self.dropped += 1;
+            return;
+        }
+        self.pending.insert(id.to_string(), entry);
+    }
+
+    fn update(&mut self, id: &str, fields: Value) {
+        if let (Some(Value::Object(entry)), Value::Object(fields)) = (self.pending.get_mut(id), fields) {
+            entry.extend(fields);
+        }
+    }
+
+    fn complete(&mut self, id: &str, fields: Value) {
+        self.update(id, fields);
+        if let Some(entry) = self.pending.remove(id) {
+            self.push(entry);
+        }
+    }
+
+    fn push(&mut self, entry: Value) {
+        if self.entries.len() == self.limit {
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+/// Records page events while a job runs; the listener task is stopped when dropped,
+/// so it never outlives the job even when it fails part way
+pub(crate) struct EventCapture {
+    buffer: Arc<Mutex<Buffer>>,
+    task: JoinHandle<()>,
+}
+
+impl EventCapture {
+    /// Record every `console.*` call as `{level, text, timestamp}`
+    pub(crate) async fn console(page: &Page) -> Result<Self, JobError> {
+        let mut events = page.event_listener::<EventConsoleApiCalled>().await
+            .map_err(|e| JobError::browser_error(format!("Console capture failed: {}", e)))?;
+        let buffer = Arc::new(Mutex::new(Buffer::new(MAX_CONSOLE_ENTRIES)));
+        let sink = Arc::clone(&buffer);
+        let task = tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                let text = event.args.iter()
+                    .map(|arg| match (&arg.value, &arg.description) {
+                        (Some(Value::String(s)), _) => s.clone(),
+                        (Some(value), _) => value.to_string(),
+                        (None, Some(description)) => description.clone(),
+                        (None, None) => arg.r#type.as_ref().to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                sink.lock().unwrap().push(json!({
+                    "level": event.r#type.as_ref(),
+                    "text": text,
+                    "timestamp": event.timestamp.inner(),
+                }));
+            }
+        });
+        Ok(Self { buffer, task })
+    }
+
+    /// Record each request as `{url, method, resource_type, status, mime_type, size}`,
+    /// with `error` instead of a size for requests that failed or were blocked
+    ///
+    /// Chromium's Network domain is already enabled for every page, so listening
+    /// adds no requests and leaves page timing alone.
+    pub(crate) async fn network(page: &Page) -> Result<Self, JobError> {
+        let listen_error = |e: chromiumoxide::error::CdpError| JobError::browser_error(format!("Network capture failed: {}", e));
+        let requests = page.event_listener::<EventRequestWillBeSent>().await.map_err(listen_error)?;
+        let responses = page.event_listener::<EventResponseReceived>().await.map_err(listen_error)?;
+        let finished = page.event_listener::<EventLoadingFinished>().await.map_err(listen_error)?;
+        let failed = page.event_listener::<EventLoadingFailed>().await.map_err(listen_error)?;
+
+        enum Network {
+            Request(Arc<EventRequestWillBeSent>),
+            Response(Arc<EventResponseReceived>),
+            Finished(Arc<EventLoadingFinished>),
+            Failed(Arc<EventLoadingFailed>),
+        }
+        let mut events = futures::stream::select(
+            futures::stream::select(requests.map(Network::Request), responses.map(Network::Response)),
+            futures::stream::select(finished.map(Network::Finished), failed.map(Network::Failed)),
+        );
+
+        let buffer = Arc::new(Mutex::new(Buffer::new(MAX_NETWORK_ENTRIES)));
+        let sink = Arc::clone(&buffer);
+        let task = tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                let mut buffer = sink.lock().unwrap();
+                match event {
+                    Network::Request(e) => buffer.start(e.request_id.inner(), json!({
+                        "url": e.request.url,
+                        "method": e.request.method,
+                        "resource_type": e.r#type.as_ref().map(|t| t.as_ref()),
+                        "status": null,
+                        "mime_type": null,
+                        "size": null,
+                    })),
+                    Network::Response(e) => buffer.update(e.request_id.inner(), json!({
+                        "url": e.response.url,
+                        "status": e.response.status,
+                        "mime_type": e.response.mime_type,
+                    })),
+                    Network::Finished(e) => buffer.complete(e.request_id.inner(), json!({
+                        "size": e.encoded_data_length as u64,
+                    })),
+                    Network::Failed(e) => buffer.complete(e.request_id.inner(), json!({
+                        "error": e.error_text,
+                        "blocked_reason": e.blocked_reason.as_ref().map(|r| r.as_ref()),
+                    })),
+                }
+            }
+        });
+        Ok(Self { buffer, task })
+    }
+
+    /// Stop listening and take what was recorded, unfinished entries last
+    /// Returns the entries and how many were dropped over the limit
+    pub(crate) fn finish(self) -> (Vec<Value>, usize) {
+        self.task.abort();
+        let mut buffer = self.buffer.lock().unwrap();
+        let mut entries: Vec<Value> = std::mem::take(&mut buffer.entries).into();
+        entries.extend(std::mem::take(&mut buffer.pending).into_values());
+        (entries, buffer.dropped)
+    }
+}
+
+impl Drop for EventCapture {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -1,5 +1,8 @@
 mod worker;
-mod actions;
-mod wait;
+mod capture;
+mod intercept;
+mod page;
+mod pool;
 
-pub use worker::ChromiumWorker;
\ No newline at end of file
+pub use worker::ChromiumWorker;
+pub use super::middleware::{ActionMiddleware, ActionContext, Next, TimingMiddleware, ScreenshotOnErrorMiddleware};
\ No newline at end of file
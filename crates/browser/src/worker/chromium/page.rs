@@ -0,0 +1,99 @@
+use async_trait::async_trait;
+use chromiumoxide::cdp::browser_protocol::dom::SetFileInputFilesParams;
+use chromiumoxide::cdp::browser_protocol::network::{CookieParam, SetCookiesParams};
+use chromiumoxide::cdp::browser_protocol::page::{CaptureScreenshotFormat, CaptureScreenshotParams, PrintToPdfParams, Viewport};
+use chromiumoxide::cdp::browser_protocol::storage::GetCookiesParams;
+use chromiumoxide::error::CdpError;
+use chromiumoxide::page::Page;
+use rocky_core::ImageFormat;
+use serde_json::Value;
+
+use crate::worker::page::{BrowserPage, PageError, ScreenshotArea, ScreenshotOptions, ScriptResult};
+
+/// Context loss shows up as these messages while the page is navigating
+fn page_error(e: CdpError) -> PageError {
+    let message = e.to_string();
+    if message.contains("Cannot find context") || message.contains("Execution context was destroyed") {
+        PageError::context_lost(message)
+    } else {
+        PageError::new(message)
+    }
+}
+
+#[async_trait]
+impl BrowserPage for Page {
+    async fn evaluate_script(&self, script: String) -> Result<ScriptResult, PageError> {
+        let result = Page::evaluate(self, script).await.map_err(page_error)?;
+        Ok(ScriptResult::new(result.value().cloned()))
+    }
+
+    async fn goto(&self, url: &str) -> Result<(), PageError> {
+        Page::goto(self, url).await.map_err(page_error)?;
+        Ok(())
+    }
+
+    async fn reload(&self) -> Result<(), PageError> {
+        Page::reload(self).await.map_err(page_error)?;
+        Ok(())
+    }
+
+    async fn screenshot(&self, options: &ScreenshotOptions) -> Result<Vec<u8>, PageError> {
+        let mut params = CaptureScreenshotParams::builder().format(match options.format {
+            ImageFormat::Png => CaptureScreenshotFormat::Png,
+            ImageFormat::Jpeg => CaptureScreenshotFormat::Jpeg,
+            ImageFormat::Webp => CaptureScreenshotFormat::Webp,
+        });
+        // Chrome rejects a quality for PNG, which is lossless anyway
+        if let Some(quality) = options.quality.filter(|_| options.format != ImageFormat::Png) {
+            params = params.quality(quality as i64);
+        }
+        match &options.area {
+            ScreenshotArea::Viewport => {}
+            ScreenshotArea::FullPage => params = params.capture_beyond_viewport(true),
+            ScreenshotArea::Element { clip, .. } => {
+                let clip = Viewport::builder()
+                    .x(clip.x)
+                    .y(clip.y)
+                    .width(clip.width)
+                    .height(clip.height)
+                    .scale(1.0)
+                    .build()
+                    .map_err(PageError::new)?;
+                params = params.clip(clip).capture_beyond_viewport(true);
+            }
+        }
+        Page::screenshot(self, params.build()).await.map_err(page_error)
+    }
+
+    async fn pdf(&self, landscape: bool, print_background: bool) -> Result<Vec<u8>, PageError> {
+        let params = PrintToPdfParams::builder()
+            .landscape(landscape)
+            .print_background(print_background)
+            .build();
+        Page::pdf(self, params).await.map_err(page_error)
+    }
+
+    async fn cookies(&self) -> Result<Vec<Value>, PageError> {
+        // Storage.getCookies covers every domain in the page's context, unlike Network.getCookies
+        let cookies = self.execute(GetCookiesParams::default()).await.map_err(page_error)?.result.cookies;
+        cookies.iter()
+            .map(|cookie| serde_json::to_value(cookie).map_err(|e| PageError::new(e.to_string())))
+            .collect()
+    }
+
+    async fn set_cookies(&self, cookies: Vec<CookieParam>) -> Result<usize, PageError> {
+        let count = cookies.len();
+        if count > 0 {
+            self.execute(SetCookiesParams::new(cookies)).await.map_err(page_error)?;
+        }
+        Ok(count)
+    }
+
+    async fn set_input_files(&self, selector: &str, files: Vec<String>) -> Result<(), PageError> {
+        let element = self.find_element(selector).await.map_err(page_error)?;
+        let mut params = SetFileInputFilesParams::new(files);
+        params.backend_node_id = Some(element.backend_node_id);
+        self.execute(params).await.map_err(page_error)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,345 @@
+use chromiumoxide::browser::{Browser, BrowserConfig as ChromeConfig};
+use chromiumoxide::browser::HeadlessMode;
+use chromiumoxide::cdp::browser_protocol::browser::BrowserContextId;
+use chromiumoxide::cdp::browser_protocol::target::{CreateBrowserContextParams, CreateTargetParams};
+use chromiumoxide::page::Page;
+use futures::StreamExt;
+use rocky_core::{BrowserConfig, JobError};
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{oneshot, Notify, OwnedSemaphorePermit, Semaphore};
+
+use crate::shared::BrowserPoolConfig;
+use super::intercept::without_credentials;
+
+/// Launch options a running browser was started with; only jobs asking for the same ones share it
+#[derive(Debug, Clone, PartialEq)]
+struct LaunchKey {
+    headless: bool,
+    viewport: Option<(u32, u32)>,
+    accept_invalid_certs: bool,
+    proxy: Option<String>,
+}
+
+impl LaunchKey {
+    fn new(config: Option<&BrowserConfig>, proxy: Option<&str>) -> Self {
+        Self {
+            headless: config.is_none_or(|c| c.headless),
+            viewport: config.and_then(|c| c.viewport_width.zip(c.viewport_height)),
+            accept_invalid_certs: config.is_some_and(|c| c.accept_invalid_certs),
+            proxy: proxy.map(str::to_string),
+        }
+    }
+}
+
+/// A running browser and the temp profile it owns
+struct PooledBrowser {
+    id: u64,
+    key: LaunchKey,
+    browser: Arc<Browser>,
+    active_pages: usize,
+    user_data_dir: PathBuf,
+}
+
+impl PooledBrowser {
+    /// Close the browser and wait for it to exit before removing its profile
+    async fn close(self) {
+        let PooledBrowser { browser, user_data_dir, .. } = self;
+        if let Ok(mut browser) = Arc::try_unwrap(browser) {
+            let _ = browser.close().await;
+            let _ = browser.wait().await;
+        }
+        let _ = tokio::fs::remove_dir_all(&user_data_dir).await;
+    }
+}
+
+struct PoolState {
+    browsers: Vec<PooledBrowser>,
+    /// Browsers being launched, counted against `max_browsers`
+    launching: usize,
+    next_id: u64,
+}
+
+/// Browsers kept running between jobs, each hosting up to `max_pages_per_browser` jobs at once
+pub(crate) struct BrowserPool {
+    config: BrowserPoolConfig,
+    state: Mutex<PoolState>,
+    pages: Arc<Semaphore>,
+    /// Signalled whenever a page slot is given back
+    released: Notify,
+}
+
+/// Outcome of one look at the pool while checking out
+enum Checkout {
+    Reuse(u64, Arc<Browser>),
+    Launch(Option<PooledBrowser>),
+    Wait,
+}
+
+impl BrowserPool {
+    pub(crate) fn new(config: BrowserPoolConfig) -> Self {
+        let max_browsers = config.max_browsers.max(1);
+        let max_pages = config.max_pages_per_browser.max(1);
+        Self {
+            config: BrowserPoolConfig { max_browsers, max_pages_per_browser: max_pages },
+            state: Mutex::new(PoolState { browsers: Vec::new(), launching: 0, next_id: 0 }),
+            pages: Arc::new(Semaphore::new(max_browsers * max_pages)),
+            released: Notify::new(),
+        }
+    }
+
+    /// Check out a page slot on a browser launched with the job's options
+    ///
+    /// Reuses a running browser with a free slot, launches one while under `max_browsers`,
+    /// or replaces an idle browser started with other options. Otherwise waits for a slot.
+    pub(crate) async fn checkout(self: &Arc<Self>, config: Option<&BrowserConfig>, proxy: Option<&str>) -> Result<BrowserLease, JobError> {
+        let key = LaunchKey::new(config, proxy);
+        let permit = Arc::clone(&self.pages).acquire_owned().await
+            .map_err(|_| JobError::browser_error("Browser pool closed"))?;
+
+        loop {
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+
+            match self.try_checkout(&key) {
+                Checkout::Reuse(id, browser) => {
+                    return Ok(BrowserLease { pool: Arc::clone(self), id, browser, _permit: permit });
+                }
+                Checkout::Launch(evicted) => {
+                    // Given back however this ends, including when the checkout is cancelled
+                    let reservation = LaunchReservation(self);
+                    let (browser, user_data_dir) = launch_detached(evicted, config, proxy).await?;
+                    let mut state = self.state.lock().unwrap();
+                    let id = state.next_id;
+                    state.next_id += 1;
+                    let browser = Arc::new(browser);
+                    state.browsers.push(PooledBrowser {
+                        id,
+                        key: key.clone(),
+                        browser: Arc::clone(&browser),
+                        active_pages: 1,
+                        user_data_dir,
+                    });
+                    drop(state);
+                    drop(reservation);
+                    return Ok(BrowserLease { pool: Arc::clone(self), id, browser, _permit: permit });
+                }
+                Checkout::Wait => released.await,
+            }
+        }
+    }
+
+    fn try_checkout(&self, key: &LaunchKey) -> Checkout {
+        let mut state = self.state.lock().unwrap();
+        let max_pages = self.config.max_pages_per_browser;
+        if let Some(pooled) = state.browsers.iter_mut().find(|b| &b.key == key && b.active_pages < max_pages) {
+            pooled.active_pages += 1;
+            return Checkout::Reuse(pooled.id, Arc::clone(&pooled.browser));
+        }
+        if state.browsers.len() + state.launching < self.config.max_browsers {
+            state.launching += 1;
+            return Checkout::Launch(None);
+        }
+        if let Some(index) = state.browsers.iter().position(|b| b.active_pages == 0) {
+            let evicted = state.browsers.remove(index);
+            state.launching += 1;
+            return Checkout::Launch(Some(evicted));
+        }
+        Checkout::Wait
+    }
+
+    fn release(&self, id: u64) {
+        if let Some(pooled) = self.state.lock().unwrap().browsers.iter_mut().find(|b| b.id == id) {
+            pooled.active_pages = pooled.active_pages.saturating_sub(1);
+        }
+        self.released.notify_waiters();
+    }
+
+    /// Drop a browser that stopped responding so later jobs launch a fresh one
+    fn discard(&self, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(index) = state.browsers.iter().position(|b| b.id == id) {
+            let pooled = state.browsers.remove(index);
+            let _ = std::fs::remove_dir_all(&pooled.user_data_dir);
+        }
+    }
+}
+
+impl Drop for BrowserPool {
+    fn drop(&mut self) {
+        // Browsers are killed when dropped, so only their profiles need removing
+        let state = self.state.get_mut().unwrap();
+        for pooled in state.browsers.drain(..) {
+            drop(pooled.browser);
+            let _ = std::fs::remove_dir_all(&pooled.user_data_dir);
+        }
+    }
+}
+
+/// A launch counted in `PoolState::launching`, released when dropped
+struct LaunchReservation<'a>(&'a BrowserPool);
+
+impl Drop for LaunchReservation<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().launching -= 1;
+        self.0.released.notify_waiters();
+    }
+}
+
+/// Close `evicted`, if any, then launch a browser, in a task of its own
+///
+/// The task runs to the end even if the caller is cancelled, e.g. by a job timeout, so the
+/// evicted browser's profile is always removed and a browser nobody is waiting for any more
+/// is closed rather than left running with its profile on disk.
+async fn launch_detached(
+    evicted: Option<PooledBrowser>,
+    config: Option<&BrowserConfig>,
+    proxy: Option<&str>,
+) -> Result<(Browser, PathBuf), JobError> {
+    let (tx, rx) = oneshot::channel();
+    let config = config.cloned();
+    let proxy = proxy.map(str::to_string);
+    tokio::spawn(async move {
+        if let Some(evicted) = evicted {
+            tracing::info!("Closing idle browser to launch one with different options");
+            evicted.close().await;
+        }
+        let launched = launch(config.as_ref(), proxy.as_deref()).await;
+        if let Err(Ok((mut browser, user_data_dir))) = tx.send(launched) {
+            let _ = browser.close().await;
+            let _ = browser.wait().await;
+            let _ = tokio::fs::remove_dir_all(&user_data_dir).await;
+        }
+    });
+    rx.await.unwrap_or_else(|_| Err(JobError::browser_error("Browser launch task failed")))
+}
+
+/// A page slot on a pooled browser, given back when dropped
+pub(crate) struct BrowserLease {
+    pool: Arc<BrowserPool>,
+    id: u64,
+    browser: Arc<Browser>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl BrowserLease {
+    /// Open a blank page in a fresh browser context, so cookies and permissions stay per job
+    pub(crate) async fn new_page(&self) -> Result<(Page, BrowserContextId), JobError> {
+        let context_id = match self.browser.create_browser_context(CreateBrowserContextParams::default()).await {
+            Ok(id) => id,
+            Err(e) => {
+                self.pool.discard(self.id);
+                return Err(JobError::browser_error(format!("Creating browser context failed: {}", e)));
+            }
+        };
+        // Disposes of the context if this is cancelled before the page is handed over
+        let mut pending = PendingContext { browser: Arc::clone(&self.browser), id: Some(context_id.clone()) };
+        let target = CreateTargetParams::builder()
+            .url("about:blank")
+            .browser_context_id(context_id.clone())
+            .build()
+            .map_err(|e| JobError::browser_error(format!("New page failed: {}", e)));
+        let page = match target {
+            Ok(target) => self.browser.new_page(target).await
+                .map_err(|e| JobError::browser_error(format!("New page failed: {}", e))),
+            Err(e) => Err(e),
+        };
+        match page {
+            Ok(page) => {
+                pending.id = None;
+                Ok((page, context_id))
+            }
+            Err(e) => {
+                // A browser that can't open a page is treated like one that can't create a context
+                pending.id = None;
+                let _ = self.browser.dispose_browser_context(context_id).await;
+                self.pool.discard(self.id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Close the job's page and dispose of its browser context
+    pub(crate) async fn close_page(&self, page: Page, context_id: BrowserContextId) {
+        let _ = page.close().await;
+        let _ = self.browser.dispose_browser_context(context_id).await;
+    }
+}
+
+/// A browser context created for a page that has not been handed over yet
+struct PendingContext {
+    browser: Arc<Browser>,
+    id: Option<BrowserContextId>,
+}
+
+impl Drop for PendingContext {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            let browser = Arc::clone(&self.browser);
+            tokio::spawn(async move {
+                let _ = browser.dispose_browser_context(id).await;
+            });
+        }
+    }
+}
+
+impl Drop for BrowserLease {
+    fn drop(&mut self) {
+        self.pool.release(self.id);
+    }
+}
+
+/// Start a browser with its own temp profile, returning it with the profile path
+async fn launch(config: Option<&BrowserConfig>, proxy: Option<&str>) -> Result<(Browser, PathBuf), JobError> {
+    let headless = config.is_none_or(|c| c.headless);
+    let temp_dir = std::env::temp_dir().join(format!("chromium-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&temp_dir)
+        .map_err(|e| JobError::config_error("user_data_dir", format!("Failed to create temp dir: {}", e))
+            .with_context(json!({ "config_field": "user_data_dir", "path": temp_dir.display().to_string() })))?;
+
+    let mut builder = ChromeConfig::builder()
+        .headless_mode(if headless { HeadlessMode::True } else { HeadlessMode::False })
+        .user_data_dir(&temp_dir);
+
+    // Chrome ignores credentials embedded in the proxy URL; they are answered per page instead
+    if let Some(proxy) = proxy {
+        builder = builder.arg(format!("--proxy-server={}", without_credentials(proxy)));
+    }
+
+    if let Some(cfg) = config {
+        if let (Some(w), Some(h)) = (cfg.viewport_width, cfg.viewport_height) {
+            if w == 0 || h == 0 {
+                let _ = std::fs::remove_dir_all(&temp_dir);
+                return Err(JobError::config_error("viewport", format!("Invalid viewport {}x{}", w, h))
+                    .with_context(json!({ "config_field": "viewport", "width": w, "height": h })));
+            }
+            builder = builder.window_size(w, h);
+        }
+        if cfg.accept_invalid_certs {
+            builder = builder.arg("--ignore-certificate-errors");
+        }
+    }
+
+    let chrome_cfg = match builder.build() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&temp_dir);
+            // The builder only fails when it cannot locate a Chrome executable
+            return Err(JobError::config_error("executable", format!("Config failed: {}", e))
+                .with_context(json!({ "config_field": "executable", "hint": "Install Chrome/Chromium or set CHROME" })));
+        }
+    };
+
+    let (browser, mut handler) = match Browser::launch(chrome_cfg).await {
+        Ok(launched) => launched,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&temp_dir);
+            return Err(JobError::browser_error(format!("Launch failed: {}", e)));
+        }
+    };
+
+    tokio::spawn(async move { while handler.next().await.is_some() {} });
+    Ok((browser, temp_dir))
+}
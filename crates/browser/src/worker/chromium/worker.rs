@@ -1,19 +1,37 @@
 use async_trait::async_trait;
-use chromiumoxide::browser::{Browser, BrowserConfig as ChromeConfig};
-use chromiumoxide::browser::HeadlessMode;
-use futures::StreamExt;
-use rocky_core::{Job, JobResult, JobError, JobWorker, Action, BrowserConfig};
+use chromiumoxide::cdp::browser_protocol::browser::{BrowserContextId, GrantPermissionsParams, PermissionType};
+use chromiumoxide::cdp::browser_protocol::network::{Headers, SetExtraHttpHeadersParams};
+use chromiumoxide::cdp::browser_protocol::page::NavigateParams;
+use rocky_parser::ParserWorker;
+use rocky_core::{Action, BrowserAction, Job, JobResult, JobError, JobWorker, RecordSink, BrowserType, Variables};
 use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+use tracing::{debug, info, instrument, warn};
 
-use super::actions::ActionHandler;
-use super::wait::WaitStrategy;
-use crate::shared::{TimeoutConfig, js};
+use crate::worker::actions::ActionHandler;
+use crate::worker::executor::{save_dom_snapshot, until_deadline, ActionExecutor};
+use crate::worker::wait::WaitStrategy;
+use crate::worker::middleware::ActionMiddleware;
+use super::pool::BrowserPool;
+use super::capture::EventCapture;
+use super::intercept::{Credentials, RequestInterceptor};
+use crate::shared::{TimeoutConfig, BrowserPoolConfig, js};
 
 pub struct ChromiumWorker {
-    browser_instances: Arc<Mutex<Vec<Browser>>>,
+    browser_pool: Arc<BrowserPool>,
     timeout_config: TimeoutConfig,
+    middleware: Vec<Arc<dyn ActionMiddleware>>,
+    record_sink: Option<Arc<dyn RecordSink>>,
+    /// Plain HTTP fetcher for `preflight` checks
+    fetcher: ParserWorker,
+}
+
+impl Default for ChromiumWorker {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ChromiumWorker {
@@ -23,35 +41,32 @@ impl ChromiumWorker {
 
     pub fn with_config(timeout_config: TimeoutConfig) -> Self {
         Self {
-            browser_instances: Arc::new(Mutex::new(vec![])),
+            browser_pool: Arc::new(BrowserPool::new(BrowserPoolConfig::default())),
             timeout_config,
+            middleware: Vec::new(),
+            record_sink: None,
+            fetcher: ParserWorker::new(),
         }
     }
 
-    async fn launch(config: Option<BrowserConfig>) -> Result<Browser, JobError> {
-        let headless = config.as_ref().map_or(true, |c| c.headless);
-        let temp_dir = std::env::temp_dir().join(format!("chromium-{}", uuid::Uuid::new_v4()));
-        std::fs::create_dir_all(&temp_dir)
-            .map_err(|e| JobError::browser_error(format!("Failed to create temp dir: {}", e)))?;
-        
-        let mut builder = ChromeConfig::builder()
-            .headless_mode(if headless { HeadlessMode::True } else { HeadlessMode::False })
-            .user_data_dir(temp_dir);
-
-        if let Some(cfg) = config {
-            if let (Some(w), Some(h)) = (cfg.viewport_width, cfg.viewport_height) {
-                builder = builder.window_size(w, h);
-            }
-        }
-
-        let chrome_cfg = builder.build()
-            .map_err(|e| JobError::browser_error(format!("Config failed: {}", e)))?;
+    /// Limit how many browsers stay running and how many jobs share each one
+    /// Jobs only share a browser launched with the same headless, viewport, certificate and proxy options
+    pub fn with_pool_config(mut self, pool_config: BrowserPoolConfig) -> Self {
+        self.browser_pool = Arc::new(BrowserPool::new(pool_config));
+        self
+    }
 
-        let (browser, mut handler) = Browser::launch(chrome_cfg).await
-            .map_err(|e| JobError::browser_error(format!("Launch failed: {}", e)))?;
+    /// Sink for extract actions with a `stream` config
+    /// Records are paged out of the page in batches, so memory stays bounded on huge pages
+    pub fn with_record_sink(mut self, sink: Arc<dyn RecordSink>) -> Self {
+        self.record_sink = Some(sink);
+        self
+    }
 
-        tokio::spawn(async move { while handler.next().await.is_some() {} });
-        Ok(browser)
+    /// Add a middleware that wraps every action; middleware run in the order added
+    pub fn with_middleware<M: ActionMiddleware + 'static>(mut self, middleware: M) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
     }
 
     async fn check_captcha(&self, page: &chromiumoxide::page::Page) -> Result<(), JobError> {
@@ -59,124 +74,284 @@ impl ChromiumWorker {
         let result = page.evaluate(js).await
             .map_err(|e| JobError::script_error(format!("CAPTCHA detection failed: {}", e)))?;
         
-        if let Some(value) = result.value() {
-            if let Some(obj) = value.as_object() {
-                // Log detection details for debugging
-                let url = obj.get("url").and_then(|v| v.as_str()).unwrap_or("unknown");
-                let detected = obj.get("detected").and_then(|v| v.as_bool()).unwrap_or(false);
+        if let Some(value) = result.value()
+            && let Some(obj) = value.as_object()
+        {
+            // Log detection details for debugging
+            let url = obj.get("url").and_then(|v| v.as_str()).unwrap_or("unknown");
+            let detected = obj.get("detected").and_then(|v| v.as_bool()).unwrap_or(false);
                 
-                println!("      URL: {}", url);
+            debug!(url, "Checked page for CAPTCHA");
                 
-                if detected {
-                    let types = obj.get("types")
-                        .and_then(|v| v.as_array())
-                        .map(|arr| arr.iter()
-                            .filter_map(|v| v.as_str())
-                            .collect::<Vec<_>>()
-                            .join(", "))
-                        .unwrap_or_else(|| "unknown".to_string());
+            if detected {
+                let types = obj.get("types")
+                    .and_then(|v| v.as_array())
+                    .map(|arr| arr.iter()
+                        .filter_map(|v| v.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "))
+                    .unwrap_or_else(|| "unknown".to_string());
                     
-                    let keywords = obj.get("keywords")
-                        .and_then(|v| v.as_array())
-                        .map(|arr| arr.iter()
-                            .filter_map(|v| v.as_str())
-                            .collect::<Vec<_>>()
-                            .join(", "))
-                        .unwrap_or_default();
+                let keywords = obj.get("keywords")
+                    .and_then(|v| v.as_array())
+                    .map(|arr| arr.iter()
+                        .filter_map(|v| v.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "))
+                    .unwrap_or_default();
                     
-                    let page_title = obj.get("pageTitle")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("unknown");
+                let page_title = obj.get("pageTitle")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("unknown");
                     
-                    let url = obj.get("url")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("unknown");
+                let url = obj.get("url")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("unknown");
                     
-                    let title_match = obj.get("titleMatch").and_then(|v| v.as_bool()).unwrap_or(false);
-                    let url_match = obj.get("urlMatch").and_then(|v| v.as_bool()).unwrap_or(false);
+                let title_match = obj.get("titleMatch").and_then(|v| v.as_bool()).unwrap_or(false);
+                let url_match = obj.get("urlMatch").and_then(|v| v.as_bool()).unwrap_or(false);
                     
-                    let body_sample = obj.get("bodyTextSample")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("");
+                let body_sample = obj.get("bodyTextSample")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
                     
-                    let message = if !keywords.is_empty() {
-                        format!("CAPTCHA or consent page detected on '{}'", page_title)
-                    } else if !types.is_empty() {
-                        format!("CAPTCHA detected on '{}' (type: {})", page_title, types)
-                    } else {
-                        format!("CAPTCHA or verification page detected on '{}'", page_title)
-                    };
+                let message = if !keywords.is_empty() {
+                    format!("CAPTCHA or consent page detected on '{}'", page_title)
+                } else if !types.is_empty() {
+                    format!("CAPTCHA detected on '{}' (type: {})", page_title, types)
+                } else {
+                    format!("CAPTCHA or verification page detected on '{}'", page_title)
+                };
                     
-                    return Err(JobError::captcha_detected(message)
-                        .with_context(json!({
-                            "types": types,
-                            "keywords": keywords,
-                            "page_title": page_title,
-                            "url": url,
-                            "title_match": title_match,
-                            "url_match": url_match,
-                            "body_sample": body_sample
-                        })));
-                }
+                return Err(JobError::captcha_detected(message)
+                    .with_context(json!({
+                        "types": types,
+                        "keywords": keywords,
+                        "page_title": page_title,
+                        "url": url,
+                        "title_match": title_match,
+                        "url_match": url_match,
+                        "body_sample": body_sample
+                    })));
             }
         }
         
         Ok(())
     }
 
-    async fn execute_actions(&self, job: &Job, page: &chromiumoxide::page::Page) -> Result<serde_json::Value, JobError> {
-        let mut output = serde_json::Map::new();
-        let fail_on_captcha = job.browser_config.as_ref().map_or(false, |c| c.fail_on_captcha);
-        let action_handler = ActionHandler::new(self.timeout_config.clone(), fail_on_captcha);
-        for (idx, action) in job.actions.iter().enumerate() {
-            println!("  [{}] Action {}/{}: {:?}", job.id, idx + 1, job.actions.len(), action);
-            
-            let result = match action {
-                Action::Scraping(a) => action_handler.handle_scraping(a, page, &mut output).await,
-                Action::Browser(a) => action_handler.handle_browser(a, page, &mut output).await,
+    /// Make `navigator.language(s)` report `locale`, returning the matching `Accept-Language` value
+    async fn override_locale(page: &chromiumoxide::page::Page, locale: &str) -> Result<String, JobError> {
+        let mut languages = vec![locale.to_string()];
+        if let Some((base, _)) = locale.split_once('-') {
+            languages.push(base.to_string());
+        }
+        let accept_language = languages.iter().enumerate()
+            .map(|(i, lang)| if i == 0 { lang.clone() } else { format!("{};q=0.9", lang) })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        page.evaluate_on_new_document(js::build_js_call(js::element::OVERRIDE_LOCALE, &[json!(languages)])).await
+            .map_err(|e| JobError::script_error(format!("Locale override failed: {}", e)))?;
+        Ok(accept_language)
+    }
+
+    /// The worker's timeouts with the job's overrides applied
+    fn timeout_config_for(&self, job: &Job) -> TimeoutConfig {
+        match job.browser_config.as_ref().and_then(|c| c.timeout_config.as_ref()) {
+            Some(overrides) => self.timeout_config.clone().with_overrides(overrides),
+            None => self.timeout_config.clone(),
+        }
+    }
+
+    /// `run_page` with the job's event captures recorded around it
+    async fn run_captured(&self, job: &Job, page: &chromiumoxide::page::Page, context_id: &BrowserContextId, current_action: &AtomicUsize) -> Result<JobResult, JobError> {
+        // Listeners start before navigation so the page's earliest logs are caught
+        let console = if job.browser_config.as_ref().is_some_and(|c| c.capture_console) {
+            Some(EventCapture::console(page).await?)
+        } else {
+            None
+        };
+        let network = if job.browser_config.as_ref().is_some_and(|c| c.capture_network) {
+            Some(EventCapture::network(page).await?)
+        } else {
+            None
+        };
+        let mut result = self.run_page(job, page, context_id, current_action).await;
+        for (key, capture) in [("console_logs", console), ("network_log", network)] {
+            let Some(capture) = capture else {
+                continue;
             };
-            
-            result.map_err(|e| {
-                eprintln!("  [{}] ✗ Action {}/{} failed", job.id, idx + 1, job.actions.len());
-                e
-            })?;
-            
-            println!("  [{}] ✓ Action {}/{} completed", job.id, idx + 1, job.actions.len());
+            let (entries, dropped) = capture.finish();
+            if dropped > 0 {
+                warn!(job_id = %job.id, dropped, key, "Dropped captured entries over the limit");
+            }
+            match &mut result {
+                Ok(r) => r.output[key] = json!(entries),
+                Err(e) => e.add_context(key, json!(entries)),
+            }
         }
-    
-        Ok(json!(output))
+        result
+    }
+
+    /// Everything from page setup to the finished result, on a page checked out of the pool
+    async fn run_page(&self, job: &Job, page: &chromiumoxide::page::Page, context_id: &BrowserContextId, current_action: &AtomicUsize) -> Result<JobResult, JobError> {
+        let mut extra_headers = serde_json::Map::new();
+        if let Some(referer) = &job.referer {
+            extra_headers.insert("Referer".to_string(), json!(referer));
+        }
+
+        if let Some(cfg) = &job.browser_config {
+            for script in &cfg.init_scripts {
+                page.evaluate_on_new_document(script.clone()).await
+                    .map_err(|e| JobError::script_error(format!("Init script failed: {}", e)))?;
+            }
+            if let Some(locale) = &cfg.locale {
+                let accept_language = Self::override_locale(page, locale).await?;
+                extra_headers.insert("Accept-Language".to_string(), json!(accept_language));
+            }
+            if !cfg.grant_permissions.is_empty() {
+                let permissions = cfg.grant_permissions.iter()
+                    .map(|name| permission_types(name))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .concat();
+                let mut grant = GrantPermissionsParams::new(permissions);
+                grant.browser_context_id = Some(context_id.clone());
+                page.execute(grant).await
+                    .map_err(|e| JobError::browser_error(format!("Granting permissions failed: {}", e)))?;
+            }
+        }
+
+        if !extra_headers.is_empty() {
+            page.execute(SetExtraHttpHeadersParams::new(Headers::new(extra_headers))).await
+                .map_err(|e| JobError::browser_error(format!("Setting extra headers failed: {}", e)))?;
+        }
+
+        // Held until the job ends so later navigations are blocked and authenticated too
+        let block_resources = job.browser_config.as_ref().map_or(&[][..], |c| &c.block_resources);
+        let basic_auth = job.browser_config.as_ref().and_then(|c| c.basic_auth.as_ref());
+        let credentials = Credentials::new(basic_auth, job.proxy.as_deref());
+        let interceptor = RequestInterceptor::start(page, block_resources, credentials).await?;
+
+        let fail_on_captcha = job.browser_config.as_ref().is_some_and(|c| c.fail_on_captcha);
+        let handler = ActionHandler::new(self.timeout_config_for(job), fail_on_captcha)
+            .with_record_sink(&job.id, self.record_sink.clone());
+        let executor = ActionExecutor { job, page, handler: &handler, middleware: &self.middleware, current_action };
+
+        // Saved sessions have to be in place before the first request is sent
+        let mut output = serde_json::Map::new();
+        // Shared by every action, so values stored before navigating are still there after
+        let mut variables = Variables::new();
+        let preloaded = job.actions.iter()
+            .take_while(|a| matches!(a, Action::Browser(BrowserAction::LoadCookies { .. })))
+            .count();
+        executor.run(0..preloaded, &mut output, &mut variables).await?;
+
+        info!(job_id = %job.id, url = %job.url, "Navigating");
+        let mut navigation = NavigateParams::new(job.url.clone());
+        navigation.referrer = job.referer.clone();
+        page.goto(navigation).await
+            .map_err(|e| JobError::navigation_error(format!("Navigation failed: {}", e)))?;
+        if let Some(interceptor) = interceptor.as_ref().filter(|i| i.auth_rejected()) {
+            return Err(JobError::navigation_error("Authentication failed: credentials were rejected")
+                .with_context(json!({ "url": job.url, "auth": interceptor.auth_summary() })));
+        }
+        
+        let timeout_config = self.timeout_config_for(job);
+        let page_stable_ms = timeout_config.page_stable.as_millis() as u64;
+        let wait_strategy = WaitStrategy::new(timeout_config);
+        wait_strategy.wait_for_stable(page, page_stable_ms).await?;
+        info!(job_id = %job.id, "Page loaded and stabilized");
+
+        // Check for CAPTCHA if configured
+        if job.browser_config.as_ref().is_some_and(|c| c.fail_on_captcha) {
+            debug!(job_id = %job.id, "Checking for CAPTCHA");
+            self.check_captcha(page).await?;
+            debug!(job_id = %job.id, "No CAPTCHA detected");
+        }
+
+        executor.run(preloaded..job.actions.len(), &mut output, &mut variables).await?;
+        let mut output = json!(output);
+
+        // Actual rendered dimensions, which can differ from the requested viewport
+        let viewport = page.evaluate(js::build_js_call(js::element::GET_VIEWPORT, &[])).await.ok();
+        output["viewport"] = viewport.and_then(|v| v.value().cloned()).unwrap_or(json!(null));
+        if let Some(auth) = interceptor.as_ref().and_then(|i| i.auth_summary()) {
+            output["auth"] = auth;
+        }
+
+        if let Some(dir) = job.browser_config.as_ref().and_then(|c| c.save_dom_snapshot.as_ref()) {
+            let path = save_dom_snapshot(page, dir, &job.id).await?;
+            output["dom_snapshot"] = json!(path);
+        }
+
+        Ok(JobResult { 
+            job_id: job.id.clone(), 
+            success: true, 
+            output,
+            tags: job.tags.clone(),
+            error: None,
+        })
+    }
+
+}
+
+/// CDP permission types behind a user-facing permission name
+fn permission_types(name: &str) -> Result<Vec<PermissionType>, JobError> {
+    match name.to_ascii_lowercase().as_str() {
+        "geolocation" => Ok(vec![PermissionType::Geolocation]),
+        "notifications" => Ok(vec![PermissionType::Notifications]),
+        "clipboard" => Ok(vec![PermissionType::ClipboardReadWrite, PermissionType::ClipboardSanitizedWrite]),
+        "camera" => Ok(vec![PermissionType::VideoCapture]),
+        "microphone" => Ok(vec![PermissionType::AudioCapture]),
+        _ => Err(JobError::config_error("grant_permissions", format!("Unknown permission '{}'", name))
+            .with_context(json!({
+                "config_field": "grant_permissions",
+                "permission": name,
+                "supported": ["geolocation", "notifications", "clipboard", "camera", "microphone"]
+            }))),
     }
 }
 
 #[async_trait]
 impl JobWorker for ChromiumWorker {
+    #[instrument(name = "chromium_job", skip_all, fields(job_id = %job.id))]
     async fn execute(&self, job: &Job) -> Result<JobResult, JobError> {
-        println!("ChromiumWorker: executing job {}", job.id);
-        let browser = Self::launch(job.browser_config.clone()).await?;
-            let page = browser.new_page("about:blank").await
-                .map_err(|e| JobError::browser_error(format!("New page failed: {}", e)))?;
-
-            println!("  [{}] Navigating to {}...", job.id, job.url);
-            page.goto(job.url.clone()).await
-                .map_err(|e| JobError::navigation_error(format!("Navigation failed: {}", e)))?;
-            
-            let wait_strategy = WaitStrategy::new(self.timeout_config.clone());
-            wait_strategy.wait_for_stable(&page, self.timeout_config.page_stable.as_millis() as u64).await?;
-            println!("  [{}] Page loaded and stabilized", job.id);
-
-            // Check for CAPTCHA if configured
-            if job.browser_config.as_ref().map_or(false, |c| c.fail_on_captcha) {
-                println!("  [{}] Checking for CAPTCHA...", job.id);
-                self.check_captcha(&page).await?;
-                println!("  [{}] ✓ No CAPTCHA detected", job.id);
-            }
+        info!("ChromiumWorker executing job");
+        job.validate()?;
+        if job.browser_config.as_ref().is_some_and(|c| c.browser_type == BrowserType::Firefox) {
+            // Running Chromium instead would silently break Firefox-specific jobs
+            return Err(JobError::browser_error("ChromiumWorker cannot run Firefox jobs; use FirefoxWorker")
+                .with_context(json!({ "config_field": "browser_type", "browser_type": "Firefox", "supported": ["Chromium"] })));
+        }
+
+        let started = Instant::now();
+        let deadline = job.timeout_ms.map(|ms| started + Duration::from_millis(ms));
+        let current_action = AtomicUsize::new(0);
+        let timed_out = |current_action: &AtomicUsize| {
+            let action = current_action.load(Ordering::Relaxed).checked_sub(1);
+            JobError::job_timeout(job.timeout_ms.unwrap_or(0), started.elapsed().as_millis() as u64, action)
+        };
 
-            let output = self.execute_actions(job, &page).await?;
+        // Safe to cancel at the deadline: an interrupted checkout gives its launch slot back and
+        // a context created for a page that was never handed over is disposed of
+        let setup = async {
+            if job.browser_config.as_ref().is_some_and(|c| c.preflight) {
+                let status = self.fetcher.preflight(&job.url, job.proxy.as_deref()).await?;
+                info!(job_id = %job.id, status, "Preflight passed");
+            }
+            let lease = self.browser_pool.checkout(job.browser_config.as_ref(), job.proxy.as_deref()).await?;
+            let (page, context_id) = lease.new_page().await?;
+            Ok::<_, JobError>((lease, page, context_id))
+        };
+        let Some(setup) = until_deadline(deadline, setup).await else {
+            return Err(timed_out(&current_action));
+        };
+        let (lease, page, context_id) = setup?;
 
-            Ok(JobResult { 
-                job_id: job.id.clone(), 
-                success: true, 
-                output 
-            })
+        // The page is closed whether the job finished or ran out of time
+        let result = until_deadline(deadline, self.run_captured(job, &page, &context_id, &current_action)).await
+            .unwrap_or_else(|| Err(timed_out(&current_action)));
+        lease.close_page(page, context_id).await;
+        result
     }
 }
@@ -0,0 +1,238 @@
+use rocky_core::{Action, BrowserAction, Job, JobError, Variables};
+use serde_json::json;
+use std::future::Future;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+use super::actions::ActionHandler;
+use super::middleware::{ActionContext, ActionMiddleware, Next};
+use super::page::BrowserPage;
+
+/// Runs a job's actions on its page through the worker's middleware, whatever the engine
+pub(crate) struct ActionExecutor<'a> {
+    pub(crate) job: &'a Job,
+    pub(crate) page: &'a dyn BrowserPage,
+    pub(crate) handler: &'a ActionHandler,
+    pub(crate) middleware: &'a [Arc<dyn ActionMiddleware>],
+    /// Kept at the running action's index plus one, so a job timeout can report it
+    pub(crate) current_action: &'a AtomicUsize,
+}
+
+impl ActionExecutor<'_> {
+    /// Run the job's actions in `range`, adding their results to `output`
+    pub(crate) async fn run(
+        &self,
+        range: Range<usize>,
+        output: &mut serde_json::Map<String, serde_json::Value>,
+        variables: &mut Variables,
+    ) -> Result<(), JobError> {
+        let job = self.job;
+        for (idx, action) in job.actions.iter().enumerate().skip(range.start).take(range.len()) {
+            info!(job_id = %job.id, action_index = idx, total = job.actions.len(), ?action, "Running action");
+            self.current_action.store(idx + 1, Ordering::Relaxed);
+
+            let result = self.run_action(idx, action, output, variables).await;
+
+            if let Err(mut e) = result {
+                error!(job_id = %job.id, action_index = idx, error = %e, "Action failed");
+                if self.handler.page_closed(self.page).await {
+                    // Every later action would fail the same way, so stop with one clear error
+                    error!(job_id = %job.id, action_index = idx, skipped = job.actions.len() - idx - 1, "Page closed, skipping remaining actions");
+                    return Err(JobError::browser_error("page closed")
+                        .with_context(json!({
+                            "failed_action": idx,
+                            "actions_not_run": job.actions.len() - idx - 1,
+                            "cause": e.message,
+                            "partial_output": output,
+                        })));
+                }
+                if job.collect_partial {
+                    e.add_context("failed_action", json!(idx));
+                    e.add_context("partial_output", json!(output));
+                }
+                return Err(e);
+            }
+
+            info!(job_id = %job.id, action_index = idx, "Action completed");
+        }
+
+        Ok(())
+    }
+
+    /// Run one action through the middleware, or for `IfPresent` each action of the branch it picks
+    /// Nested actions report the index of the job-level action they belong to
+    ///
+    /// Variables are substituted just before the action runs, so each sees what earlier ones stored.
+    /// That happens inside `Optional`, so an unresolved variable only skips that action.
+    async fn run_action(
+        &self,
+        index: usize,
+        action: &Action,
+        output: &mut serde_json::Map<String, serde_json::Value>,
+        variables: &mut Variables,
+    ) -> Result<(), JobError> {
+        let job = self.job;
+        if let Action::Optional(inner) = action {
+            if let Err(e) = Box::pin(self.run_action(index, inner, output, variables)).await {
+                warn!(job_id = %job.id, action_index = index, error = %e, "Optional action failed, continuing");
+                output.insert(format!("error:{}", index), json!(e));
+            }
+            return Ok(());
+        }
+        let action = &variables.substitute(action)?;
+        if let Action::Browser(BrowserAction::IfPresent { selector, then, else_ }) = action {
+            let present = self.handler.is_present(self.page, selector).await?;
+            info!(job_id = %job.id, action_index = index, selector = %selector, present,
+                actions = if present { then.len() } else { else_.len() }, "Running IfPresent branch");
+            output.insert(format!("if_present:{}", selector), json!(present));
+            for nested in if present { then } else { else_ } {
+                Box::pin(self.run_action(index, nested, output, variables)).await?;
+            }
+            return Ok(());
+        }
+        let ctx = ActionContext { job_id: &job.id, index, action, page: self.page };
+        Next::new(self.middleware, self.handler, output).run(&ctx).await?;
+        variables.capture(action, output);
+        Ok(())
+    }
+}
+
+/// Await `fut`, giving up at `deadline` if there is one; `None` when the deadline passed first
+pub(crate) async fn until_deadline<T>(deadline: Option<Instant>, fut: impl Future<Output = T>) -> Option<T> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, fut).await.ok(),
+        None => Some(fut.await),
+    }
+}
+
+/// Write the rendered document's HTML to `{dir}/{job_id}.html`, returning the path
+pub(crate) async fn save_dom_snapshot(page: &dyn BrowserPage, dir: &str, job_id: &str) -> Result<String, JobError> {
+    let result = page.evaluate("document.documentElement.outerHTML").await
+        .map_err(|e| JobError::script_error(format!("DOM snapshot failed: {}", e)))?;
+    let html = result.value().and_then(|v| v.as_str()).unwrap_or("").to_string();
+
+    tokio::fs::create_dir_all(dir).await
+        .map_err(|e| JobError::config_error("save_dom_snapshot", format!("Failed to create snapshot dir: {}", e)))?;
+    let path = std::path::Path::new(dir).join(format!("{}.html", job_id));
+    tokio::fs::write(&path, html).await
+        .map_err(|e| JobError::browser_error(format!("Failed to save DOM snapshot: {}", e)))?;
+    Ok(path.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use chromiumoxide::cdp::browser_protocol::network::CookieParam;
+    use serde_json::Value;
+    use std::sync::Mutex;
+
+    use crate::shared::{js, TimeoutConfig};
+    use crate::worker::page::{PageError, ScreenshotOptions, ScriptResult};
+
+    /// A page whose scripts evaluate to `reply`, or to their own source without one, recording every script it was given
+    #[derive(Default)]
+    struct FakePage {
+        scripts: Mutex<Vec<String>>,
+        reply: Option<Value>,
+    }
+
+    #[async_trait]
+    impl BrowserPage for FakePage {
+        async fn evaluate_script(&self, script: String) -> Result<ScriptResult, PageError> {
+            self.scripts.lock().unwrap().push(script.clone());
+            Ok(ScriptResult::new(Some(self.reply.clone().unwrap_or(json!(script)))))
+        }
+
+        async fn goto(&self, _url: &str) -> Result<(), PageError> {
+            Err(PageError::new("not supported by FakePage"))
+        }
+
+        async fn reload(&self) -> Result<(), PageError> {
+            Err(PageError::new("not supported by FakePage"))
+        }
+
+        async fn screenshot(&self, _options: &ScreenshotOptions) -> Result<Vec<u8>, PageError> {
+            Err(PageError::new("not supported by FakePage"))
+        }
+
+        async fn pdf(&self, _landscape: bool, _print_background: bool) -> Result<Vec<u8>, PageError> {
+            Err(PageError::new("not supported by FakePage"))
+        }
+
+        async fn cookies(&self) -> Result<Vec<Value>, PageError> {
+            Ok(Vec::new())
+        }
+
+        async fn set_cookies(&self, cookies: Vec<CookieParam>) -> Result<usize, PageError> {
+            Ok(cookies.len())
+        }
+
+        async fn set_input_files(&self, _selector: &str, _files: Vec<String>) -> Result<(), PageError> {
+            Err(PageError::new("not supported by FakePage"))
+        }
+    }
+
+    fn job(actions: Value) -> Job {
+        serde_json::from_value(json!({
+            "id": "job-1",
+            "url": "https://example.com",
+            "use_browser": true,
+            "actions": actions,
+            "browser_config": null,
+        })).unwrap()
+    }
+
+    /// Run every action of `job` on `page`, returning the output
+    async fn run(job: &Job, page: &FakePage) -> Result<serde_json::Map<String, Value>, JobError> {
+        let handler = ActionHandler::new(TimeoutConfig::default(), false);
+        let current_action = AtomicUsize::new(0);
+        let executor = ActionExecutor { job, page, handler: &handler, middleware: &[], current_action: &current_action };
+        let mut output = serde_json::Map::new();
+        executor.run(0..job.actions.len(), &mut output, &mut Variables::new()).await?;
+        Ok(output)
+    }
+
+    #[tokio::test]
+    async fn optional_action_with_an_unresolved_variable_is_skipped() {
+        let job = job(json!([
+            { "Optional": { "Browser": { "Click": { "selector": "${missing}", "timeout_ms": 100 } } } },
+            { "Browser": { "ExecuteScript": { "script": "document.title" } } },
+        ]));
+        let page = FakePage::default();
+        let output = run(&job, &page).await.unwrap();
+        assert_eq!(output["error:0"]["category"], "Config");
+        assert_eq!(*page.scripts.lock().unwrap(), ["document.title"]);
+    }
+
+    #[tokio::test]
+    async fn unnamed_script_results_accumulate_in_order() {
+        let job = job(json!([
+            { "Browser": { "ExecuteScript": { "script": "document.title" } } },
+            { "Browser": { "ExecuteScript": { "script": "location.href", "name": "href" } } },
+            { "Browser": { "ExecuteScript": { "script": "document.body.innerText" } } },
+        ]));
+        let page = FakePage::default();
+        let output = run(&job, &page).await.unwrap();
+        assert_eq!(output["execute_script"], json!(["document.title", "document.body.innerText"]));
+        assert_eq!(output["script:href"], "location.href");
+    }
+
+    #[tokio::test]
+    async fn set_cookie_passes_quotes_and_semicolons_verbatim() {
+        let value = r#"a"b'c; domain=evil.test"#;
+        let job = job(json!([
+            { "Browser": { "SetCookie": { "name": "session", "value": value, "domain": null } } },
+        ]));
+        let page = FakePage { reply: Some(json!({ "success": true })), ..Default::default() };
+        let output = run(&job, &page).await.unwrap();
+
+        let expected = js::build_js_call(js::element::SET_COOKIE, &[json!("session"), json!(value), json!({ "domain": null })]);
+        assert_eq!(*page.scripts.lock().unwrap(), [expected]);
+        assert!(page.scripts.lock().unwrap()[0].contains(r#"("session", "a\"b'c; domain=evil.test", {"domain":null})"#));
+        assert_eq!(output["set_cookie:session"], value);
+    }
+}
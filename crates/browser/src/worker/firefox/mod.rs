@@ -0,0 +1,4 @@
+mod page;
+mod worker;
+
+pub use worker::FirefoxWorker;
\ No newline at end of file
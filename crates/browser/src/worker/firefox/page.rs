@@ -0,0 +1,248 @@
+use async_trait::async_trait;
+use base64::prelude::*;
+use chromiumoxide::cdp::browser_protocol::network::CookieParam;
+use reqwest::{Client, Method};
+use rocky_core::{ImageFormat, JobError};
+use serde_json::{json, Value};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::process::{Child, Command};
+use tracing::debug;
+
+use crate::worker::page::{BrowserPage, PageError, ScreenshotArea, ScreenshotOptions, ScriptResult};
+
+/// Key of the element reference in a WebDriver response
+const ELEMENT_KEY: &str = "element-6066-11e4-a52e-4f735466cecf";
+
+/// How long geckodriver gets to start accepting connections
+const DRIVER_STARTUP: Duration = Duration::from_secs(10);
+
+/// A Firefox window driven over WebDriver by a geckodriver process of its own
+///
+/// geckodriver serves one session at a time, so every page gets its own driver and browser.
+/// `close` ends the session, which quits Firefox; a page dropped without closing, such as at a
+/// job deadline, ends it in the background instead.
+pub(crate) struct FirefoxPage {
+    client: Client,
+    /// `http://127.0.0.1:{port}/session/{id}`
+    session: String,
+    driver: Option<Child>,
+}
+
+impl FirefoxPage {
+    /// Start geckodriver from the `geckodriver` path and open a Firefox session with `capabilities`
+    pub(crate) async fn launch(geckodriver: &str, capabilities: Value) -> Result<Self, JobError> {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .map_err(|e| JobError::browser_error(format!("No free port for geckodriver: {}", e)))?
+            .port();
+        let mut driver = Command::new(geckodriver)
+            .arg("--port")
+            .arg(port.to_string())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| JobError::browser_error(format!("Failed to start geckodriver: {}", e))
+                .with_context(json!({ "geckodriver": geckodriver, "hint": "Install geckodriver or set its path with FirefoxWorker::with_geckodriver" })))?;
+
+        let client = Client::new();
+        let base = format!("http://127.0.0.1:{}", port);
+        wait_until_ready(&client, &base, &mut driver).await?;
+
+        let body = json!({ "capabilities": { "alwaysMatch": capabilities } });
+        let session = send(&client, Method::POST, &format!("{}/session", base), Some(body)).await
+            .map_err(|e| JobError::browser_error(format!("Firefox failed to start: {}", e))
+                .with_context(json!({ "hint": "Install Firefox or put its binary on PATH" })))?;
+        let Some(id) = session.get("sessionId").and_then(|v| v.as_str()) else {
+            return Err(JobError::browser_error("Firefox failed to start: geckodriver returned no session id"));
+        };
+        Ok(Self { client, session: format!("{}/session/{}", base, id), driver: Some(driver) })
+    }
+
+    /// Quit Firefox and stop geckodriver
+    pub(crate) async fn close(mut self) {
+        if let Some(driver) = self.driver.take() {
+            end_session(self.client.clone(), self.session.clone(), driver).await;
+        }
+    }
+
+    /// Send a command to this session; `path` is relative to the session URL
+    async fn command(&self, method: Method, path: &str, body: Option<Value>) -> Result<Value, PageError> {
+        send(&self.client, method, &format!("{}{}", self.session, path), body).await
+    }
+
+    /// WebDriver id of the first element matching a CSS `selector`
+    async fn find_element(&self, selector: &str) -> Result<String, PageError> {
+        let element = self.command(Method::POST, "/element", Some(json!({ "using": "css selector", "value": selector }))).await?;
+        element.get(ELEMENT_KEY)
+            .and_then(|v| v.as_str())
+            .map(String::from)
+            .ok_or_else(|| PageError::new(format!("no such element: {}", selector)))
+    }
+
+    /// Decode a base64 screenshot or PDF
+    async fn binary(&self, method: Method, path: &str, body: Option<Value>) -> Result<Vec<u8>, PageError> {
+        let data = self.command(method, path, body).await?;
+        BASE64_STANDARD.decode(data.as_str().unwrap_or_default())
+            .map_err(|e| PageError::new(format!("Invalid base64 from geckodriver: {}", e)))
+    }
+}
+
+impl Drop for FirefoxPage {
+    fn drop(&mut self) {
+        if let Some(driver) = self.driver.take() {
+            tokio::spawn(end_session(self.client.clone(), std::mem::take(&mut self.session), driver));
+        }
+    }
+}
+
+/// End the session, which quits Firefox, then stop geckodriver
+async fn end_session(client: Client, session: String, mut driver: Child) {
+    if let Err(e) = send(&client, Method::DELETE, &session, None).await {
+        debug!(error = %e, "Failed to end WebDriver session");
+    }
+    let _ = driver.kill().await;
+}
+
+/// Poll geckodriver's status endpoint until it answers, failing if it exits first
+async fn wait_until_ready(client: &Client, base: &str, driver: &mut Child) -> Result<(), JobError> {
+    let started = Instant::now();
+    loop {
+        if let Ok(Some(status)) = driver.try_wait() {
+            return Err(JobError::browser_error(format!("geckodriver exited on startup with {}", status)));
+        }
+        if client.get(format!("{}/status", base)).send().await.is_ok() {
+            return Ok(());
+        }
+        if started.elapsed() > DRIVER_STARTUP {
+            return Err(JobError::browser_error(format!("geckodriver did not start within {}s", DRIVER_STARTUP.as_secs())));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Send a WebDriver command, returning the `value` of its response
+async fn send(client: &Client, method: Method, url: &str, body: Option<Value>) -> Result<Value, PageError> {
+    let mut request = client.request(method, url);
+    if let Some(body) = body {
+        request = request.json(&body);
+    }
+    let response = request.send().await
+        .map_err(|e| PageError::new(format!("WebDriver request failed: {}", e)))?;
+    let status = response.status();
+    let mut body: Value = response.json().await
+        .map_err(|e| PageError::new(format!("Invalid WebDriver response: {}", e)))?;
+    let value = body.get_mut("value").map(Value::take).unwrap_or(Value::Null);
+    if status.is_success() {
+        return Ok(value);
+    }
+
+    let error = value.get("error").and_then(|v| v.as_str()).unwrap_or("unknown error");
+    let message = format!("{}: {}", error, value.get("message").and_then(|v| v.as_str()).unwrap_or(""));
+    // A script cut off by a navigation fails this way while the next document loads
+    if message.contains("unloaded") || message.contains("destroyed") {
+        Err(PageError::context_lost(message))
+    } else {
+        Err(PageError::new(message))
+    }
+}
+
+/// A WebDriver cookie as CDP reports it, so saved cookie files are the same for every engine
+fn cdp_cookie(cookie: &Value) -> Value {
+    let text = |key: &str| cookie.get(key).and_then(|v| v.as_str()).unwrap_or("");
+    let flag = |key: &str| cookie.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+    let expiry = cookie.get("expiry").and_then(|v| v.as_f64());
+    json!({
+        "name": text("name"),
+        "value": text("value"),
+        "domain": text("domain"),
+        "path": text("path"),
+        "expires": expiry.unwrap_or(-1.0),
+        "size": text("name").len() + text("value").len(),
+        "httpOnly": flag("httpOnly"),
+        "secure": flag("secure"),
+        "session": expiry.is_none(),
+        "sameSite": cookie.get("sameSite"),
+    })
+}
+
+/// A CDP cookie in the shape WebDriver's add cookie command takes
+fn webdriver_cookie(cookie: &CookieParam) -> Result<Value, PageError> {
+    let cookie = serde_json::to_value(cookie).map_err(|e| PageError::new(e.to_string()))?;
+    let mut converted = serde_json::Map::new();
+    for key in ["name", "value", "domain", "path", "secure", "httpOnly", "sameSite"] {
+        if let Some(value) = cookie.get(key).filter(|v| !v.is_null()) {
+            converted.insert(key.to_string(), value.clone());
+        }
+    }
+    if let Some(expires) = cookie.get("expires").and_then(|v| v.as_f64()).filter(|t| *t >= 0.0) {
+        converted.insert("expiry".to_string(), json!(expires as u64));
+    }
+    Ok(Value::Object(converted))
+}
+
+#[async_trait]
+impl BrowserPage for FirefoxPage {
+    async fn evaluate_script(&self, script: String) -> Result<ScriptResult, PageError> {
+        // Indirect eval runs the script globally and yields its last value, as CDP's evaluate does
+        let body = json!({ "script": "return (0, eval)(arguments[0]);", "args": [script] });
+        let value = self.command(Method::POST, "/execute/sync", Some(body)).await?;
+        Ok(ScriptResult::new(Some(value).filter(|v| !v.is_null())))
+    }
+
+    async fn goto(&self, url: &str) -> Result<(), PageError> {
+        self.command(Method::POST, "/url", Some(json!({ "url": url }))).await?;
+        Ok(())
+    }
+
+    async fn reload(&self) -> Result<(), PageError> {
+        self.command(Method::POST, "/refresh", Some(json!({}))).await?;
+        Ok(())
+    }
+
+    async fn screenshot(&self, options: &ScreenshotOptions) -> Result<Vec<u8>, PageError> {
+        if options.format != ImageFormat::Png {
+            return Err(PageError::new("Firefox only takes PNG screenshots"));
+        }
+        match &options.area {
+            ScreenshotArea::Viewport => self.binary(Method::GET, "/screenshot", None).await,
+            ScreenshotArea::FullPage => self.binary(Method::GET, "/moz/screenshot/full", None).await,
+            ScreenshotArea::Element { selector, .. } => {
+                let element = self.find_element(selector).await?;
+                self.binary(Method::GET, &format!("/element/{}/screenshot", element), None).await
+            }
+        }
+    }
+
+    async fn pdf(&self, landscape: bool, print_background: bool) -> Result<Vec<u8>, PageError> {
+        let orientation = if landscape { "landscape" } else { "portrait" };
+        self.binary(Method::POST, "/print", Some(json!({ "orientation": orientation, "background": print_background }))).await
+    }
+
+    async fn cookies(&self) -> Result<Vec<Value>, PageError> {
+        let cookies = self.command(Method::GET, "/cookie", None).await?;
+        Ok(cookies.as_array().map(|cookies| cookies.iter().map(cdp_cookie).collect()).unwrap_or_default())
+    }
+
+    async fn set_cookies(&self, cookies: Vec<CookieParam>) -> Result<usize, PageError> {
+        let mut set = 0;
+        for cookie in &cookies {
+            match self.command(Method::POST, "/cookie", Some(json!({ "cookie": webdriver_cookie(cookie)? }))).await {
+                Ok(_) => set += 1,
+                // WebDriver only sets cookies for the open document's domain
+                Err(e) if e.to_string().starts_with("invalid cookie domain") => {
+                    debug!(cookie = %cookie.name, domain = ?cookie.domain, "Skipping cookie for another domain");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(set)
+    }
+
+    async fn set_input_files(&self, selector: &str, files: Vec<String>) -> Result<(), PageError> {
+        let element = self.find_element(selector).await?;
+        self.command(Method::POST, &format!("/element/{}/value", element), Some(json!({ "text": files.join("\n") }))).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+use async_trait::async_trait;
+use rocky_parser::ParserWorker;
+use rocky_core::{Action, BrowserAction, Job, JobResult, JobError, JobWorker, RecordSink, Variables};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::time::{Duration, Instant};
+use tracing::{debug, info, instrument};
+
+use crate::worker::actions::ActionHandler;
+use crate::worker::executor::{save_dom_snapshot, until_deadline, ActionExecutor};
+use crate::worker::middleware::ActionMiddleware;
+use crate::worker::page::BrowserPage;
+use crate::worker::wait::WaitStrategy;
+use crate::shared::{TimeoutConfig, js};
+use super::page::FirefoxPage;
+
+/// Runs browser jobs in Firefox, driven over WebDriver by geckodriver
+///
+/// Actions behave as in `ChromiumWorker`; the job options that need the DevTools protocol
+/// (init scripts, permissions, event capture, request blocking and auth, referer) fail with a
+/// config error. Every job it is given runs in Firefox, so to mix engines register it with
+/// the scheduler's `with_firefox_worker` and it only gets jobs whose `browser_type` is `Firefox`.
+pub struct FirefoxWorker {
+    geckodriver: String,
+    timeout_config: TimeoutConfig,
+    middleware: Vec<Arc<dyn ActionMiddleware>>,
+    record_sink: Option<Arc<dyn RecordSink>>,
+    /// Firefox instances allowed at once; each job launches its own
+    sessions: Semaphore,
+    /// Plain HTTP fetcher for `preflight` checks
+    fetcher: ParserWorker,
+}
+
+impl Default for FirefoxWorker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FirefoxWorker {
+    pub fn new() -> Self {
+        Self::with_config(TimeoutConfig::default())
+    }
+
+    pub fn with_config(timeout_config: TimeoutConfig) -> Self {
+        Self {
+            geckodriver: "geckodriver".to_string(),
+            timeout_config,
+            middleware: Vec::new(),
+            record_sink: None,
+            sessions: Semaphore::new(2),
+            fetcher: ParserWorker::new(),
+        }
+    }
+
+    /// Path to the geckodriver binary, by default `geckodriver` on `PATH`
+    pub fn with_geckodriver(mut self, path: &str) -> Self {
+        self.geckodriver = path.to_string();
+        self
+    }
+
+    /// Limit how many Firefox instances run at once; jobs over the limit wait for a free one
+    pub fn with_max_sessions(mut self, max: usize) -> Self {
+        self.sessions = Semaphore::new(max.max(1));
+        self
+    }
+
+    /// Sink for extract actions with a `stream` config
+    /// Records are paged out of the page in batches, so memory stays bounded on huge pages
+    pub fn with_record_sink(mut self, sink: Arc<dyn RecordSink>) -> Self {
+        self.record_sink = Some(sink);
+        self
+    }
+
+    /// Add a middleware that wraps every action; middleware run in the order added
+    pub fn with_middleware<M: ActionMiddleware + 'static>(mut self, middleware: M) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// The worker's timeouts with the job's overrides applied
+    fn timeout_config_for(&self, job: &Job) -> TimeoutConfig {
+        match job.browser_config.as_ref().and_then(|c| c.timeout_config.as_ref()) {
+            Some(overrides) => self.timeout_config.clone().with_overrides(overrides),
+            None => self.timeout_config.clone(),
+        }
+    }
+
+    /// Everything from navigation to the finished result, on a freshly launched Firefox
+    async fn run_page(&self, job: &Job, page: &dyn BrowserPage, current_action: &AtomicUsize) -> Result<JobResult, JobError> {
+        let timeout_config = self.timeout_config_for(job);
+        let page_stable_ms = timeout_config.page_stable.as_millis() as u64;
+        let fail_on_captcha = job.browser_config.as_ref().is_some_and(|c| c.fail_on_captcha);
+        let handler = ActionHandler::new(timeout_config.clone(), fail_on_captcha)
+            .with_record_sink(&job.id, self.record_sink.clone());
+        let executor = ActionExecutor { job, page, handler: &handler, middleware: &self.middleware, current_action };
+        let mut output = serde_json::Map::new();
+        // Shared by every action, so values stored while loading cookies are still there after
+        let mut variables = Variables::new();
+
+        info!(job_id = %job.id, url = %job.url, "Navigating");
+        page.goto(&job.url).await
+            .map_err(|e| JobError::navigation_error(format!("Navigation failed: {}", e)))?;
+
+        // WebDriver only sets cookies for the open document, so saved sessions are loaded once
+        // the page is open and take effect on a reload
+        let preloaded = job.actions.iter()
+            .take_while(|a| matches!(a, Action::Browser(BrowserAction::LoadCookies { .. })))
+            .count();
+        if preloaded > 0 {
+            executor.run(0..preloaded, &mut output, &mut variables).await?;
+            page.reload().await
+                .map_err(|e| JobError::navigation_error(format!("Reload after loading cookies failed: {}", e)))?;
+        }
+
+        WaitStrategy::new(timeout_config).wait_for_stable(page, page_stable_ms).await?;
+        info!(job_id = %job.id, "Page loaded and stabilized");
+
+        if fail_on_captcha {
+            debug!(job_id = %job.id, "Checking for CAPTCHA");
+            handler.check_captcha(page).await?;
+            debug!(job_id = %job.id, "No CAPTCHA detected");
+        }
+
+        executor.run(preloaded..job.actions.len(), &mut output, &mut variables).await?;
+        let mut output = json!(output);
+
+        // Actual rendered dimensions, which can differ from the requested viewport
+        let viewport = page.evaluate(js::build_js_call(js::element::GET_VIEWPORT, &[])).await.ok();
+        output["viewport"] = viewport.and_then(|v| v.value().cloned()).unwrap_or(json!(null));
+
+        if let Some(dir) = job.browser_config.as_ref().and_then(|c| c.save_dom_snapshot.as_ref()) {
+            let path = save_dom_snapshot(page, dir, &job.id).await?;
+            output["dom_snapshot"] = json!(path);
+        }
+
+        Ok(JobResult {
+            job_id: job.id.clone(),
+            success: true,
+            output,
+            tags: job.tags.clone(),
+            error: None,
+        })
+    }
+}
+
+/// Fail jobs that set options only `ChromiumWorker` can honor, rather than ignore them
+fn check_supported(job: &Job) -> Result<(), JobError> {
+    let mut unsupported = Vec::new();
+    if job.referer.is_some() {
+        unsupported.push("referer");
+    }
+    if job.proxy.as_deref().is_some_and(|p| p.split_once("://").map_or(p, |(_, rest)| rest).contains('@')) {
+        unsupported.push("proxy credentials");
+    }
+    if let Some(cfg) = &job.browser_config {
+        let options = [
+            ("init_scripts", !cfg.init_scripts.is_empty()),
+            ("grant_permissions", !cfg.grant_permissions.is_empty()),
+            ("capture_console", cfg.capture_console),
+            ("capture_network", cfg.capture_network),
+            ("block_resources", !cfg.block_resources.is_empty()),
+            ("basic_auth", cfg.basic_auth.is_some()),
+        ];
+        unsupported.extend(options.into_iter().filter(|(_, set)| *set).map(|(name, _)| name));
+    }
+    match unsupported.first() {
+        None => Ok(()),
+        Some(field) => Err(JobError::config_error(*field, format!("FirefoxWorker does not support {}; use ChromiumWorker", unsupported.join(", ")))
+            .with_context(json!({ "config_field": field, "unsupported": unsupported }))),
+    }
+}
+
+/// WebDriver capabilities for the job's Firefox
+fn capabilities(job: &Job, timeouts: &TimeoutConfig) -> Result<Value, JobError> {
+    let config = job.browser_config.as_ref();
+    let mut args = Vec::new();
+    if config.is_none_or(|c| c.headless) {
+        args.push("-headless".to_string());
+    }
+    if let Some((w, h)) = config.and_then(|c| c.viewport_width.zip(c.viewport_height)) {
+        if w == 0 || h == 0 {
+            return Err(JobError::config_error("viewport", format!("Invalid viewport {}x{}", w, h))
+                .with_context(json!({ "config_field": "viewport", "width": w, "height": h })));
+        }
+        args.push(format!("--window-size={},{}", w, h));
+    }
+
+    // Sent as Accept-Language and reported by navigator.language(s)
+    let mut prefs = serde_json::Map::new();
+    if let Some(locale) = config.and_then(|c| c.locale.as_deref()) {
+        let mut languages = vec![locale];
+        if let Some((base, _)) = locale.split_once('-') {
+            languages.push(base);
+        }
+        prefs.insert("intl.accept_languages".to_string(), json!(languages.join(",")));
+    }
+
+    let mut capabilities = json!({
+        "browserName": "firefox",
+        "acceptInsecureCerts": config.is_some_and(|c| c.accept_invalid_certs),
+        // Scripts such as network idle waits bound themselves, like they do over CDP
+        "timeouts": { "script": null, "pageLoad": timeouts.navigation.as_millis() as u64, "implicit": 0 },
+        "moz:firefoxOptions": { "args": args, "prefs": prefs },
+    });
+    if let Some(proxy) = &job.proxy {
+        capabilities["proxy"] = proxy_capability(proxy)?;
+    }
+    Ok(capabilities)
+}
+
+/// WebDriver proxy settings for a proxy URL such as `http://host:port` or `socks5://host:port`
+fn proxy_capability(proxy: &str) -> Result<Value, JobError> {
+    let (scheme, host) = proxy.split_once("://").unwrap_or(("http", proxy));
+    let host = host.trim_end_matches('/');
+    match scheme.to_ascii_lowercase().as_str() {
+        "http" | "https" => Ok(json!({ "proxyType": "manual", "httpProxy": host, "sslProxy": host })),
+        "socks4" => Ok(json!({ "proxyType": "manual", "socksProxy": host, "socksVersion": 4 })),
+        "socks5" | "socks5h" => Ok(json!({ "proxyType": "manual", "socksProxy": host, "socksVersion": 5 })),
+        _ => Err(JobError::config_error("proxy", format!("Unsupported proxy scheme '{}'", scheme))
+            .with_context(json!({ "config_field": "proxy", "supported": ["http", "https", "socks4", "socks5"] }))),
+    }
+}
+
+#[async_trait]
+impl JobWorker for FirefoxWorker {
+    #[instrument(name = "firefox_job", skip_all, fields(job_id = %job.id))]
+    async fn execute(&self, job: &Job) -> Result<JobResult, JobError> {
+        info!("FirefoxWorker executing job");
+        job.validate()?;
+        check_supported(job)?;
+        let capabilities = capabilities(job, &self.timeout_config_for(job))?;
+
+        let started = Instant::now();
+        let deadline = job.timeout_ms.map(|ms| started + Duration::from_millis(ms));
+        let current_action = AtomicUsize::new(0);
+        let timed_out = |current_action: &AtomicUsize| {
+            let action = current_action.load(Ordering::Relaxed).checked_sub(1);
+            JobError::job_timeout(job.timeout_ms.unwrap_or(0), started.elapsed().as_millis() as u64, action)
+        };
+
+        // Safe to cancel at the deadline: a page dropped before it is closed quits in the background
+        let setup = async {
+            if job.browser_config.as_ref().is_some_and(|c| c.preflight) {
+                let status = self.fetcher.preflight(&job.url, job.proxy.as_deref()).await?;
+                info!(job_id = %job.id, status, "Preflight passed");
+            }
+            let permit = self.sessions.acquire().await
+                .map_err(|e| JobError::browser_error(format!("No Firefox session available: {}", e)))?;
+            let page = FirefoxPage::launch(&self.geckodriver, capabilities).await?;
+            Ok::<_, JobError>((permit, page))
+        };
+        let Some(setup) = until_deadline(deadline, setup).await else {
+            return Err(timed_out(&current_action));
+        };
+        let (_permit, page) = setup?;
+
+        // Firefox is quit whether the job finished or ran out of time
+        let result = until_deadline(deadline, self.run_page(job, &page, &current_action)).await
+            .unwrap_or_else(|| Err(timed_out(&current_action)));
+        page.close().await;
+        result
+    }
+}
@@ -0,0 +1,129 @@
+use async_trait::async_trait;
+use rocky_core::{Action, BrowserAction, JobError};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::shared::js;
+use super::actions::ActionHandler;
+use super::page::{BrowserPage, ScreenshotOptions};
+
+/// Information about the action being run, passed to each middleware
+pub struct ActionContext<'a> {
+    pub job_id: &'a str,
+    /// Zero-based position of the action in the job
+    pub index: usize,
+    pub action: &'a Action,
+    pub page: &'a dyn BrowserPage,
+}
+
+/// Wraps the execution of every action in a job
+///
+/// Middleware run in the order they were added; each one decides whether and
+/// when to call `next.run(ctx)` to continue down the chain to the action itself.
+#[async_trait]
+pub trait ActionMiddleware: Send + Sync {
+    async fn around(&self, ctx: &ActionContext<'_>, next: Next<'_>) -> Result<(), JobError>;
+}
+
+/// The rest of the middleware chain, ending with the action handler
+pub struct Next<'a> {
+    middleware: &'a [Arc<dyn ActionMiddleware>],
+    handler: &'a ActionHandler,
+    output: &'a mut Map<String, Value>,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(
+        middleware: &'a [Arc<dyn ActionMiddleware>],
+        handler: &'a ActionHandler,
+        output: &'a mut Map<String, Value>,
+    ) -> Self {
+        Self { middleware, handler, output }
+    }
+
+    pub async fn run(self, ctx: &ActionContext<'_>) -> Result<(), JobError> {
+        match self.middleware.split_first() {
+            Some((first, rest)) => {
+                first.around(ctx, Next::new(rest, self.handler, self.output)).await
+            }
+            None => self.handler.handle(ctx.action, ctx.page, self.output).await,
+        }
+    }
+}
+
+/// Logs how long each action took
+pub struct TimingMiddleware;
+
+#[async_trait]
+impl ActionMiddleware for TimingMiddleware {
+    async fn around(&self, ctx: &ActionContext<'_>, next: Next<'_>) -> Result<(), JobError> {
+        let start = Instant::now();
+        let result = next.run(ctx).await;
+        info!(job_id = ctx.job_id, action_index = ctx.index, elapsed_ms = start.elapsed().as_millis() as u64, "Action timed");
+        result
+    }
+}
+
+/// Saves a screenshot to `dir` whenever an action fails
+pub struct ScreenshotOnErrorMiddleware {
+    pub dir: String,
+    /// Outline the element a failed Click, Type or WaitAndClick targeted in the screenshot
+    pub highlight_target: bool,
+}
+
+impl ScreenshotOnErrorMiddleware {
+    pub fn new(dir: &str) -> Self {
+        std::fs::create_dir_all(dir).ok(); // ensure folder exists
+        Self { dir: dir.to_string(), highlight_target: false }
+    }
+
+    pub fn with_highlight(mut self) -> Self {
+        self.highlight_target = true;
+        self
+    }
+}
+
+/// Selector of the element an interactive action acts on
+fn target_selector(action: &Action) -> Option<&str> {
+    match action {
+        Action::Browser(BrowserAction::Click { selector, .. })
+        | Action::Browser(BrowserAction::Type { selector, .. })
+        | Action::Browser(BrowserAction::WaitAndClick { selector, .. }) => Some(selector),
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl ActionMiddleware for ScreenshotOnErrorMiddleware {
+    async fn around(&self, ctx: &ActionContext<'_>, next: Next<'_>) -> Result<(), JobError> {
+        let result = next.run(ctx).await;
+        if result.is_err() {
+            let path = std::path::Path::new(&self.dir)
+                .join(format!("{}-action-{}.png", ctx.job_id, ctx.index + 1));
+            let highlighted = match target_selector(ctx.action).filter(|_| self.highlight_target) {
+                Some(selector) => {
+                    let js = js::build_js_call(js::element::HIGHLIGHT_ELEMENT, &[json!(selector)]);
+                    ctx.page.evaluate(js).await.is_ok()
+                }
+                None => false,
+            };
+            let screenshot = ctx.page.screenshot(&ScreenshotOptions::default()).await;
+            if highlighted {
+                let _ = ctx.page.evaluate(js::build_js_call(js::element::CLEAR_HIGHLIGHT, &[])).await;
+            }
+            match screenshot {
+                Ok(bytes) => {
+                    if let Err(e) = tokio::fs::write(&path, &bytes).await {
+                        warn!(job_id = ctx.job_id, action_index = ctx.index, error = %e, "Failed to save error screenshot");
+                    } else {
+                        info!(job_id = ctx.job_id, action_index = ctx.index, path = %path.display(), "Error screenshot saved");
+                    }
+                }
+                Err(e) => warn!(job_id = ctx.job_id, action_index = ctx.index, error = %e, "Failed to capture error screenshot"),
+            }
+        }
+        result
+    }
+}
@@ -1,3 +1,12 @@
 pub mod chromium;
+pub mod firefox;
+mod actions;
+mod executor;
+mod middleware;
+mod page;
+mod wait;
 
-pub use chromium::ChromiumWorker as BrowserWorker;
\ No newline at end of file
+pub use chromium::ChromiumWorker as BrowserWorker;
+pub use firefox::FirefoxWorker;
+pub use middleware::{ActionMiddleware, ActionContext, Next, TimingMiddleware, ScreenshotOnErrorMiddleware};
+pub use page::{BrowserPage, PageError, ScriptResult, ScreenshotOptions, ScreenshotArea, Clip};
\ No newline at end of file
@@ -0,0 +1,115 @@
+use async_trait::async_trait;
+use chromiumoxide::cdp::browser_protocol::network::CookieParam;
+use rocky_core::ImageFormat;
+use serde_json::Value;
+
+/// A failed page operation
+#[derive(Debug, Clone)]
+pub struct PageError {
+    message: String,
+    context_lost: bool,
+}
+
+impl PageError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self { message: message.into(), context_lost: false }
+    }
+
+    /// A script that failed because the document went away under it, as happens mid-navigation
+    pub fn context_lost(message: impl Into<String>) -> Self {
+        Self { message: message.into(), context_lost: true }
+    }
+
+    /// Whether the page was navigating, so the same call is likely to work once it settles
+    pub fn is_context_lost(&self) -> bool {
+        self.context_lost
+    }
+}
+
+impl std::fmt::Display for PageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for PageError {}
+
+/// What a script evaluated to
+#[derive(Debug, Clone, Default)]
+pub struct ScriptResult(Option<Value>);
+
+impl ScriptResult {
+    pub fn new(value: Option<Value>) -> Self {
+        Self(value)
+    }
+
+    /// The returned value; `None` for `undefined` or anything that is not JSON
+    pub fn value(&self) -> Option<&Value> {
+        self.0.as_ref()
+    }
+}
+
+/// A rectangle of the page in CSS pixels, relative to the top left of the document
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clip {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// The part of the page a screenshot covers
+#[derive(Debug, Clone, Default)]
+pub enum ScreenshotArea {
+    /// What is currently scrolled into view
+    #[default]
+    Viewport,
+    /// The whole document, beyond the viewport
+    FullPage,
+    /// The first element matching `selector`, measured at `clip`
+    Element { selector: String, clip: Clip },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScreenshotOptions {
+    pub format: ImageFormat,
+    /// Compression quality from 0 to 100; ignored for PNG
+    pub quality: Option<u8>,
+    pub area: ScreenshotArea,
+}
+
+/// The page operations actions are built from, implemented for each browser engine
+///
+/// Almost every action runs a script from `shared::js`, so an engine mostly has to evaluate
+/// scripts the way a devtools console would; the rest are things scripts cannot do.
+#[async_trait]
+pub trait BrowserPage: Send + Sync {
+    /// Evaluate `script` in the page, awaiting the result if it is a promise
+    async fn evaluate_script(&self, script: String) -> Result<ScriptResult, PageError>;
+
+    /// Navigate to `url` and wait for it to load
+    async fn goto(&self, url: &str) -> Result<(), PageError>;
+
+    async fn reload(&self) -> Result<(), PageError>;
+
+    /// Encoded image bytes in `options.format`
+    async fn screenshot(&self, options: &ScreenshotOptions) -> Result<Vec<u8>, PageError>;
+
+    async fn pdf(&self, landscape: bool, print_background: bool) -> Result<Vec<u8>, PageError>;
+
+    /// Every cookie the browser holds for the page, across all domains, as CDP `Cookie` objects
+    async fn cookies(&self) -> Result<Vec<Value>, PageError>;
+
+    /// Set `cookies`, returning how many were set
+    async fn set_cookies(&self, cookies: Vec<CookieParam>) -> Result<usize, PageError>;
+
+    /// Choose `files` (absolute paths) in the first file input matching `selector`
+    async fn set_input_files(&self, selector: &str, files: Vec<String>) -> Result<(), PageError>;
+}
+
+impl dyn BrowserPage + '_ {
+    /// `evaluate_script` for anything that converts into a script
+    pub async fn evaluate(&self, script: impl Into<String>) -> Result<ScriptResult, PageError> {
+        self.evaluate_script(script.into()).await
+    }
+}
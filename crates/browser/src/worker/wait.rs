@@ -0,0 +1,334 @@
+use rocky_core::{JobError, WaitCriteria};
+use serde_json::json;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::{debug, warn};
+use crate::shared::{js, to_job_error, TimeoutConfig};
+use super::page::BrowserPage;
+
+pub struct WaitStrategy {
+    config: TimeoutConfig,
+}
+
+impl WaitStrategy {
+    pub fn new(config: TimeoutConfig) -> Self {
+        Self { config }
+    }
+
+    /// The configured page stabilization timeout, for waits that follow a navigation
+    pub fn page_stable_ms(&self) -> u64 {
+        self.config.page_stable.as_millis() as u64
+    }
+
+    /// The configured element wait timeout, for actions that take no timeout of their own
+    pub fn element_wait_ms(&self) -> u64 {
+        self.config.element_wait.as_millis() as u64
+    }
+
+    pub async fn wait_for_element(
+        &self,
+        page: &dyn BrowserPage,
+        selector: &str,
+        timeout_ms: u64,
+        check_clickable: bool,
+    ) -> Result<(), JobError> {
+        let criteria = WaitCriteria {
+            require_enabled: Some(check_clickable),
+            require_stable_ms: None,
+        };
+        self.wait_for_element_with(page, selector, timeout_ms, &criteria).await
+    }
+
+    /// Wait for an element using explicit criteria
+    /// `require_enabled` defaults to false when unset
+    pub async fn wait_for_element_with(
+        &self,
+        page: &dyn BrowserPage,
+        selector: &str,
+        timeout_ms: u64,
+        criteria: &WaitCriteria,
+    ) -> Result<(), JobError> {
+        self.wait_until_ready(page, selector, timeout_ms, criteria, false).await
+    }
+
+    /// Wait for an element that may be inside open shadow roots
+    pub async fn wait_for_shadow_element(&self, page: &dyn BrowserPage, selector: &str, timeout_ms: u64) -> Result<(), JobError> {
+        self.wait_until_ready(page, selector, timeout_ms, &WaitCriteria::default(), true).await
+    }
+
+    async fn wait_until_ready(
+        &self,
+        page: &dyn BrowserPage,
+        selector: &str,
+        timeout_ms: u64,
+        criteria: &WaitCriteria,
+        pierce_shadow: bool,
+    ) -> Result<(), JobError> {
+        let check_clickable = criteria.require_enabled.unwrap_or(false);
+        let mut stable_since: Option<(String, Instant)> = None;
+        let timeout = std::time::Duration::from_millis(timeout_ms);
+        let start = Instant::now();
+        let selector_json = json!(selector);
+        let mut last_state = String::new();
+        
+        loop {
+            let js = js::build_query_js_call(js::element::CHECK_ELEMENT_STATE, std::slice::from_ref(&selector_json), pierce_shadow);
+            
+            // Handle potential context loss gracefully
+            let result = match page.evaluate(js).await {
+                Ok(r) => r,
+                Err(e) => {
+                    if e.is_context_lost() {
+                        // Page is navigating, wait a bit and retry
+                        sleep(Duration::from_millis(500)).await;
+                        continue;
+                    }
+                    return Err(to_job_error(e, "WaitFor"));
+                }
+            };
+            
+            if let Some(state) = result.value()
+                && let Some(obj) = state.as_object()
+            {
+                let exists = obj.get("exists").and_then(|v| v.as_bool()).unwrap_or(false);
+                let visible = obj.get("visible").and_then(|v| v.as_bool()).unwrap_or(false);
+                let obscured = obj.get("obscured").and_then(|v| v.as_bool()).unwrap_or(false);
+                let disabled = obj.get("disabled").and_then(|v| v.as_bool()).unwrap_or(false);
+                    
+                let current_state = format!("exists:{} visible:{} obscured:{} disabled:{}", 
+                    exists, visible, obscured, disabled);
+                    
+                if current_state != last_state {
+                    debug!(selector, state = %current_state, "Element state changed");
+                    last_state = current_state;
+                }
+                    
+                if !exists {
+                    if start.elapsed() > timeout {
+                        return Err(JobError::element_not_found(
+                            format!("Element '{}' not found after {}ms", selector, timeout_ms)
+                        ).with_context(json!({ "selector": selector, "timeout_ms": timeout_ms })));
+                    }
+                } else if !visible {
+                    if start.elapsed() > timeout {
+                        return Err(JobError::element_not_found(
+                            format!("Element '{}' exists but not visible", selector)
+                        ).with_context(json!({ "selector": selector, "hint": "Element may be hidden with CSS" })));
+                    }
+                } else if obscured {
+                    let obscured_by = obj.get("obscuredBy").and_then(|v| v.as_str()).unwrap_or("unknown");
+                    if start.elapsed() > timeout {
+                        return Err(JobError::element_not_found(
+                            format!("Element '{}' obscured by {}", selector, obscured_by)
+                        ).with_context(json!({ 
+                            "selector": selector,
+                            "obscured_by": obscured_by,
+                            "suggestion": "Use HandleCookieBanner or WaitAndClick" 
+                        })));
+                    }
+                } else if check_clickable && disabled {
+                    if start.elapsed() > timeout {
+                        return Err(JobError::element_not_found(
+                            format!("Element '{}' is disabled", selector)
+                        ));
+                    }
+                } else if let Some(stable_ms) = criteria.require_stable_ms {
+                    let rect = obj.get("rect").map(|v| v.to_string()).unwrap_or_default();
+                    match &stable_since {
+                        Some((last_rect, since)) if *last_rect == rect => {
+                            if since.elapsed() >= Duration::from_millis(stable_ms) {
+                                debug!(selector, "Element ready and stable");
+                                return Ok(());
+                            }
+                        }
+                        _ => stable_since = Some((rect, Instant::now())),
+                    }
+                    if start.elapsed() > timeout {
+                        return Err(JobError::timeout_error(
+                            format!("Element '{}' did not stay stable for {}ms", selector, stable_ms)
+                        ).with_context(json!({ "selector": selector, "timeout_ms": timeout_ms, "stable_ms": stable_ms })));
+                    }
+                } else {
+                    debug!(selector, "Element ready");
+                    return Ok(());
+                }
+            }
+            
+            if start.elapsed() > timeout {
+                return Err(JobError::timeout_error(
+                    format!("Timeout waiting for element '{}'", selector)
+                ).with_context(json!({ "selector": selector, "timeout_ms": timeout_ms })));
+            }
+            
+            sleep(self.config.check_interval).await;
+        }
+    }
+    
+    /// Whether the element is fully inside the viewport; false if it does not exist
+    pub async fn is_in_viewport(&self, page: &dyn BrowserPage, selector: &str) -> Result<bool, JobError> {
+        let js = js::build_js_call(js::element::CHECK_ELEMENT_STATE, &[json!(selector)]);
+        let result = page.evaluate(js).await
+            .map_err(|e| to_job_error(e, "IsInViewport"))?;
+        Ok(result.value()
+            .and_then(|v| v.get("inViewport"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false))
+    }
+    
+    pub async fn wait_for_stable(&self, page: &dyn BrowserPage, timeout_ms: u64) -> Result<(), JobError> {
+        let timeout = std::time::Duration::from_millis(timeout_ms);
+        let start = Instant::now();
+        let mut stable_checks = 0;
+        let required_stable_checks = 5;
+        
+        debug!("Waiting for page to stabilize");
+        
+        // First, wait a bit for the navigation to start
+        sleep(Duration::from_millis(500)).await;
+        
+        loop {
+            let js = js::build_js_call(js::wait::CHECK_LOADING, &[]);
+            
+            // Handle context loss gracefully during navigation
+            let result = match page.evaluate(js).await {
+                Ok(r) => r,
+                Err(e) => {
+                    if e.is_context_lost() {
+                        debug!("Page context changed while navigating, waiting");
+                        stable_checks = 0;
+                        sleep(Duration::from_millis(1000)).await;
+                        continue;
+                    }
+                    return Err(to_job_error(e, "WaitForStable"));
+                }
+            };
+            
+            if let Some(state) = result.value()
+                && let Some(obj) = state.as_object()
+            {
+                let ready = obj.get("readyState").and_then(|v| v.as_str()) == Some("complete");
+                let active = obj.get("activeRequests").and_then(|v| v.as_u64()).unwrap_or(0);
+                    
+                if ready && active == 0 {
+                    stable_checks += 1;
+                    if stable_checks >= required_stable_checks {
+                        debug!(elapsed_ms = start.elapsed().as_millis() as u64, "Page stabilized");
+                        sleep(self.config.settle_delay).await;
+                        return Ok(());
+                    }
+                } else {
+                    if stable_checks > 0 {
+                        debug!(ready, active, "Page activity detected, resetting");
+                    }
+                    stable_checks = 0;
+                }
+            }
+            
+            if start.elapsed() > timeout {
+                warn!(timeout_ms, "Page did not stabilize in time, continuing anyway");
+                return Ok(()); // Don't fail, just continue
+            }
+            
+            sleep(self.config.check_interval).await;
+        }
+    }
+    
+    /// Poll the page URL until it matches `pattern`, returning the final URL
+    pub async fn wait_for_url(&self, page: &dyn BrowserPage, pattern: &str, timeout_ms: u64) -> Result<String, JobError> {
+        let timeout = Duration::from_millis(timeout_ms);
+        let start = Instant::now();
+        let js = js::build_js_call(js::wait::MATCH_URL, &[json!(pattern)]);
+        let mut last_url = String::new();
+        
+        loop {
+            match page.evaluate(js.clone()).await {
+                Ok(result) => {
+                    if let Some(obj) = result.value().and_then(|v| v.as_object()) {
+                        let href = obj.get("href").and_then(|v| v.as_str()).unwrap_or("");
+                        if href != last_url {
+                            debug!(url = href, "Current URL");
+                            last_url = href.to_string();
+                        }
+                        if obj.get("matches").and_then(|v| v.as_bool()) == Some(true) {
+                            debug!(pattern, "URL matches");
+                            return Ok(last_url);
+                        }
+                    }
+                }
+                Err(e) => {
+                    if !e.is_context_lost() {
+                        return Err(to_job_error(e, "WaitForUrl"));
+                    }
+                    // Page is navigating, keep polling
+                }
+            }
+            
+            if start.elapsed() > timeout {
+                return Err(JobError::timeout_error(
+                    format!("URL did not match '{}' within {}ms", pattern, timeout_ms)
+                ).with_context(json!({ "pattern": pattern, "last_url": last_url, "timeout_ms": timeout_ms })));
+            }
+            
+            sleep(self.config.check_interval).await;
+        }
+    }
+    
+    /// Poll the match count for a selector until it is unchanged across `stable_checks` polls
+    /// Returns the final count
+    pub async fn wait_for_stable_count(&self, page: &dyn BrowserPage, selector: &str, stable_checks: u32, timeout_ms: u64) -> Result<u64, JobError> {
+        let timeout = Duration::from_millis(timeout_ms);
+        let start = Instant::now();
+        let js = js::build_js_call(js::wait::COUNT_ELEMENTS, &[json!(selector)]);
+        let mut last_count: Option<u64> = None;
+        let mut unchanged = 0;
+
+        debug!(selector, "Waiting for element count to stabilize");
+
+        loop {
+            match page.evaluate(js.clone()).await {
+                Ok(result) => {
+                    let count = result.value().and_then(|v| v.as_u64()).unwrap_or(0);
+                    if last_count == Some(count) {
+                        unchanged += 1;
+                        if unchanged >= stable_checks {
+                            debug!(selector, count, elapsed_ms = start.elapsed().as_millis() as u64, "Element count stable");
+                            return Ok(count);
+                        }
+                    } else {
+                        if last_count.is_some() {
+                            debug!(selector, count, "Element count changed, resetting");
+                        }
+                        last_count = Some(count);
+                        unchanged = 0;
+                    }
+                }
+                Err(e) => {
+                    if !e.is_context_lost() {
+                        return Err(to_job_error(e, "WaitForStableCount"));
+                    }
+                    // Page is navigating, start counting again once it settles
+                    last_count = None;
+                    unchanged = 0;
+                }
+            }
+
+            if start.elapsed() > timeout {
+                return Err(JobError::timeout_error(
+                    format!("Count for '{}' did not stabilize within {}ms", selector, timeout_ms)
+                ).with_context(json!({ "selector": selector, "last_count": last_count, "stable_checks": stable_checks, "timeout_ms": timeout_ms })));
+            }
+
+            sleep(self.config.check_interval).await;
+        }
+    }
+
+    pub async fn wait_for_navigation(&self, page: &dyn BrowserPage, timeout_ms: u64) -> Result<(), JobError> {
+        debug!("Waiting for navigation");
+        
+        // Wait a moment for navigation to actually start
+        sleep(Duration::from_millis(1000)).await;
+        
+        // Now wait for it to complete
+        self.wait_for_stable(page, timeout_ms).await
+    }
+}
\ No newline at end of file
@@ -0,0 +1,243 @@
+use scraper::Selector;
+use scraper::error::SelectorErrorKind;
+use selectors::parser::SelectorParseErrorKind;
+
+use crate::JobError;
+
+/// Pseudo-classes that depend on live browser state and can't be evaluated on static HTML
+pub const DYNAMIC_PSEUDO_CLASSES: &[&str] = &[
+    "hover", "focus", "focus-visible", "focus-within", "active", "visited", "target",
+    "placeholder-shown", "autofill", "valid", "invalid", "user-valid", "user-invalid",
+    "in-range", "out-of-range", "read-only", "read-write", "indeterminate", "default",
+    "defined", "fullscreen", "playing", "paused",
+];
+
+/// Static-HTML equivalents for pseudo-classes scraper doesn't understand
+const STATIC_EQUIVALENTS: &[(&str, &str)] = &[
+    ("checked", ":is([checked], option[selected])"),
+    ("disabled", "[disabled]"),
+    ("enabled", ":not([disabled])"),
+    ("any-link", ":is(a, area)[href]"),
+    ("link", ":is(a, area)[href]"),
+    ("required", "[required]"),
+    ("optional", ":not([required])"),
+];
+
+/// Check that a selector compiles as valid CSS
+/// Pseudo-classes only a browser can evaluate are accepted
+/// Returns a parsing error carrying the offending selector if it does not
+pub fn validate_selector(selector: &str) -> Result<(), JobError> {
+    let rewritten = rewrite_pseudo_classes(selector, |name| {
+        if DYNAMIC_PSEUDO_CLASSES.contains(&name) {
+            Some(":is(*)".to_string())
+        } else {
+            static_equivalent(name)
+        }
+    });
+    parse(selector, &rewritten).map(|_| ())
+}
+
+/// Parse a selector for matching against static HTML
+///
+/// Pseudo-classes with a static equivalent (`:checked`, `:disabled`, `:link`, ...)
+/// are rewritten to attribute selectors. Ones that need live browser state
+/// (`:hover`, `:focus`, ...) fail with a hint to run the job in a browser.
+pub fn parse_static_selector(selector: &str) -> Result<Selector, JobError> {
+    if let Some(name) = find_pseudo_classes(selector)
+        .into_iter()
+        .find(|name| DYNAMIC_PSEUDO_CLASSES.contains(name))
+    {
+        return Err(JobError::parsing_error(format!(
+            "Selector '{}' uses :{} which depends on live browser state",
+            selector, name
+        ))
+        .with_context(serde_json::json!({
+            "selector": selector,
+            "pseudo_class": name,
+            "hint": "Run this job with use_browser: true"
+        })));
+    }
+
+    let rewritten = rewrite_pseudo_classes(selector, static_equivalent);
+    parse(selector, &rewritten)
+}
+
+fn parse(original: &str, rewritten: &str) -> Result<Selector, JobError> {
+    Selector::parse(rewritten).map_err(|e| {
+        JobError::parsing_error(format!("Invalid selector '{}': {}", original, describe_error(original, &e)))
+            .with_context(serde_json::json!({ "selector": original }))
+    })
+}
+
+/// scraper's own message for errors it does not expect asks for a bug report and dumps
+/// its internals, so those are described here instead
+fn describe_error(original: &str, error: &SelectorErrorKind) -> String {
+    match error {
+        SelectorErrorKind::UnexpectedSelectorParseError(SelectorParseErrorKind::UnsupportedPseudoClassOrElement(name)) => {
+            if original.contains(&format!("::{}", name)) {
+                format!("unsupported pseudo-element '::{}'", name)
+            } else {
+                format!("unsupported pseudo-class ':{}'", name)
+            }
+        }
+        SelectorErrorKind::UnexpectedSelectorParseError(_) => "unsupported selector syntax".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn static_equivalent(name: &str) -> Option<String> {
+    STATIC_EQUIVALENTS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, replacement)| replacement.to_string())
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+/// Names of the argument-less pseudo-classes used in a selector
+fn find_pseudo_classes(selector: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    rewrite_pseudo_classes(selector, |name| {
+        names.push(name);
+        None
+    });
+    names
+}
+
+/// Walk the selector and offer each argument-less pseudo-class (`:name` not followed by `(`)
+/// to `replace`, skipping quoted strings, attribute selectors and pseudo-elements
+fn rewrite_pseudo_classes<'a>(
+    selector: &'a str,
+    mut replace: impl FnMut(&'a str) -> Option<String>,
+) -> String {
+    let mut out = String::with_capacity(selector.len());
+    let mut quote: Option<char> = None;
+    let mut in_brackets = false;
+    let mut i = 0;
+
+    while i < selector.len() {
+        let c = selector[i..].chars().next().unwrap();
+        if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            }
+        } else if c == '"' || c == '\'' {
+            quote = Some(c);
+        } else if c == '[' {
+            in_brackets = true;
+        } else if c == ']' {
+            in_brackets = false;
+        } else if c == ':' && !in_brackets {
+            let rest = &selector[i + 1..];
+            if let Some(stripped) = rest.strip_prefix(':') {
+                // Pseudo-element: copy through untouched
+                let len = stripped.find(|c: char| !is_ident_char(c)).unwrap_or(stripped.len());
+                out.push_str(&selector[i..i + 2 + len]);
+                i += 2 + len;
+                continue;
+            }
+            let len = rest.find(|c: char| !is_ident_char(c)).unwrap_or(rest.len());
+            let name = &rest[..len];
+            let has_args = rest[len..].starts_with('(');
+            if !name.is_empty() && !has_args
+                && let Some(replacement) = replace(name)
+            {
+                out.push_str(&replacement);
+                i += 1 + len;
+                continue;
+            }
+        }
+        out.push(c);
+        i += c.len_utf8();
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Job;
+
+    fn job(use_browser: bool, selector: &str) -> Job {
+        serde_json::from_value(serde_json::json!({
+            "id": "job-1",
+            "url": "https://example.com",
+            "use_browser": use_browser,
+            "actions": [
+                { "Scraping": { "Extract": { "selector": "h1", "attr": null } } },
+                { "Scraping": { "Extract": { "selector": selector, "attr": null } } },
+            ],
+            "browser_config": null,
+        })).unwrap()
+    }
+
+    #[test]
+    fn malformed_selectors_fail_with_the_offending_selector() {
+        for selector in ["div[", "p >", "a:foo"] {
+            let error = validate_selector(selector).unwrap_err();
+            assert_eq!(error.category, crate::ErrorCategory::Parsing, "{}", selector);
+            assert_eq!(error.context["selector"], selector);
+            assert!(error.message.starts_with(&format!("Invalid selector '{}'", selector)), "{}", error.message);
+        }
+    }
+
+    #[test]
+    fn unsupported_pseudo_classes_are_named_without_scraper_internals() {
+        let error = validate_selector("a:foo").unwrap_err();
+        assert_eq!(error.message, "Invalid selector 'a:foo': unsupported pseudo-class ':foo'");
+
+        let error = parse_static_selector("p::before").unwrap_err();
+        assert_eq!(error.message, "Invalid selector 'p::before': unsupported pseudo-element '::before'");
+    }
+
+    #[test]
+    fn job_validate_checks_selectors_for_both_workers() {
+        for use_browser in [true, false] {
+            assert!(job(use_browser, "ul > li.item").validate().is_ok());
+            let error = job(use_browser, "div[").validate().unwrap_err();
+            assert_eq!(error.context["selector"], "div[");
+        }
+        // Browser-only pseudo-classes pass for a browser job and fail for a parser job
+        assert!(job(true, "a:hover").validate().is_ok());
+        assert!(job(false, "a:hover").validate().is_err());
+        // A selector built from a variable is only known at run time
+        assert!(job(false, "${selector}").validate().is_ok());
+    }
+
+    #[test]
+    fn browser_state_pseudo_classes_are_rejected_with_a_hint() {
+        for (selector, name) in [("a:hover", "hover"), ("input:focus", "focus"), ("li > a:hover span", "hover")] {
+            let error = parse_static_selector(selector).unwrap_err();
+            assert_eq!(error.message, format!("Selector '{}' uses :{} which depends on live browser state", selector, name));
+            assert_eq!(error.context["pseudo_class"], name);
+            assert_eq!(error.context["hint"], "Run this job with use_browser: true");
+        }
+    }
+
+    #[test]
+    fn static_equivalents_match_the_same_elements_as_a_browser() {
+        let html = scraper::Html::parse_fragment(r#"
+            <input id="on" type="checkbox" checked>
+            <input id="off" type="checkbox">
+            <select><option id="picked" selected>A</option><option id="other">B</option></select>
+            <button id="disabled" disabled>Save</button>
+            <button id="enabled">Cancel</button>
+            <a id="link" href="/next">Next</a>
+            <a id="anchor" name="top">Top</a>
+            <area id="area" href="/map">
+        "#);
+        let ids = |selector: &str| -> Vec<String> {
+            let selector = parse_static_selector(selector).unwrap();
+            html.select(&selector).filter_map(|el| el.value().id().map(String::from)).collect()
+        };
+        assert_eq!(ids(":checked"), ["on", "picked"]);
+        assert_eq!(ids("button:disabled"), ["disabled"]);
+        assert_eq!(ids("button:enabled"), ["enabled"]);
+        assert_eq!(ids(":link"), ["link", "area"]);
+        // Inside quotes and attribute values a colon is left alone
+        assert_eq!(ids(r#"a[href=":checked"], a[title='a:hover']"#), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,129 @@
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+use crate::{Action, JobError, ScrapingAction};
+
+/// Fields of an action that may reference variables as `${name}`
+const SUBSTITUTED_FIELDS: &[&str] = &["text", "url", "selector"];
+
+/// Nested action lists that are substituted when they run rather than with their parent
+const DEFERRED_FIELDS: &[&str] = &["then", "else"];
+
+/// Values saved by `store_as` during a job, for later actions to reference as `${name}`
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    values: HashMap<String, Value>,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    /// Whether a string contains a `${name}` reference
+    pub fn has_reference(s: &str) -> bool {
+        s.find("${").is_some_and(|start| s[start..].contains('}'))
+    }
+
+    /// Save the output of an action that has `store_as` set
+    /// Call after the action ran, with the same (substituted) action
+    pub fn capture(&mut self, action: &Action, output: &Map<String, Value>) {
+        let stored = match action {
+            Action::Optional(inner) => return self.capture(inner, output),
+            Action::Scraping(ScrapingAction::Extract { selector, store_as: Some(name), .. }) => {
+                Some((name, output.get(&format!("extract:{}", selector)).cloned()))
+            }
+            Action::Scraping(ScrapingAction::ExtractOne { selector, store_as: Some(name), .. }) => {
+                Some((name, output.get(&format!("extract_one:{}", selector)).cloned()))
+            }
+            Action::Scraping(ScrapingAction::ExtractFirst { selectors, store_as: Some(name), .. }) => {
+                let key = format!("extract_first:{}", selectors.join(", "));
+                Some((name, output.get(&key).and_then(|v| v.get("values")).cloned()))
+            }
+            _ => None,
+        };
+        if let Some((name, value)) = stored {
+            self.values.insert(name.clone(), value.unwrap_or(Value::Null));
+        }
+    }
+
+    /// A copy of `action` with every `${name}` in its `text`, `url` and `selector` fields
+    /// replaced by the stored value
+    ///
+    /// Extracted lists stand for their first item. A name that was never stored, or whose
+    /// extract matched nothing, is an error rather than being sent as the literal `${name}`.
+    pub fn substitute(&self, action: &Action) -> Result<Action, JobError> {
+        let mut value = serde_json::to_value(action)
+            .map_err(|e| JobError::config_error("actions", format!("Could not read action: {}", e)))?;
+        if !self.substitute_value(&mut value)? {
+            return Ok(action.clone());
+        }
+        serde_json::from_value(value)
+            .map_err(|e| JobError::config_error("actions", format!("Invalid action after substitution: {}", e)))
+    }
+
+    /// Substitute in place, returning whether anything changed
+    fn substitute_value(&self, value: &mut Value) -> Result<bool, JobError> {
+        let mut changed = false;
+        match value {
+            Value::Object(fields) => {
+                for (key, field) in fields.iter_mut() {
+                    if DEFERRED_FIELDS.contains(&key.as_str()) {
+                        continue;
+                    }
+                    match field {
+                        Value::String(s) if SUBSTITUTED_FIELDS.contains(&key.as_str()) && Self::has_reference(s) => {
+                            *s = self.substitute_str(s)?;
+                            changed = true;
+                        }
+                        _ => changed |= self.substitute_value(field)?,
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    changed |= self.substitute_value(item)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(changed)
+    }
+
+    fn substitute_str(&self, s: &str) -> Result<String, JobError> {
+        let mut result = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(start) = rest.find("${") {
+            let Some(len) = rest[start + 2..].find('}') else {
+                break;
+            };
+            let name = &rest[start + 2..start + 2 + len];
+            result.push_str(&rest[..start]);
+            result.push_str(&self.resolve(name)?);
+            rest = &rest[start + 3 + len..];
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    fn resolve(&self, name: &str) -> Result<String, JobError> {
+        let value = match self.values.get(name) {
+            Some(Value::Array(items)) => items.first(),
+            other => other,
+        };
+        match value {
+            Some(Value::String(s)) => Ok(s.clone()),
+            Some(Value::Null) | None => Err(JobError::config_error("actions", format!("Unresolved variable '${{{}}}'", name))
+                .with_context(json!({
+                    "variable": name,
+                    "stored": self.values.contains_key(name),
+                    "available": self.values.keys().collect::<Vec<_>>(),
+                }))),
+            Some(other) => Ok(other.to_string()),
+        }
+    }
+}
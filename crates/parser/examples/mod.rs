@@ -2,7 +2,6 @@ use rocky_core::{Action, Job, ScrapingAction};
 use rocky_parser::ParserWorker;
 use rocky_scheduler::Scheduler;
 use rocky_storage::JsonFileStorage;
-use tokio::time::{Duration, sleep};
 
 #[tokio::main]
 async fn main() {
@@ -24,21 +23,37 @@ async fn main() {
                 Action::Scraping(ScrapingAction::WaitFor {
                     selector: "h1".to_string(),
                     timeout_ms: 5000,
+                    pierce_shadow: false,
                 }),
                 Action::Scraping(ScrapingAction::Extract {
                     selector: "p".to_string(),
                     attr: None,
+                    limit: None,
+                    offset: None,
+                    resolve_urls: false,
+                    pierce_shadow: false,
+                    store_as: None,
                 }),
                 Action::Scraping(ScrapingAction::ExtractMultiple {
                     selector: "a".to_string(),
                     attrs: vec!["href".to_string(), "text".to_string()],
+                    limit: None,
+                    offset: None,
+                    resolve_urls: false,
+                    pierce_shadow: false,
+                    stream: None,
                 }),
             ],
             browser_config: None,
+            tags: Vec::new(),
+            proxy: None,
+            referer: None,
+            priority: 0,
+            timeout_ms: None,
+            collect_partial: false,
         };
-        scheduler.submit(job).unwrap();
-        sleep(Duration::from_millis(200)).await;
+        scheduler.submit_async(job).await.unwrap();
     }
 
-    sleep(Duration::from_secs(15)).await;
+    scheduler.wait_idle().await;
 }
@@ -0,0 +1,96 @@
+use encoding_rs::{Encoding, UTF_8};
+
+/// How far into the body to look for a `<meta>` charset, as browsers do
+const META_PRESCAN_BYTES: usize = 1024;
+
+/// Decode a response body using the charset from a byte order mark, the `Content-Type` header or
+/// a `<meta>` tag, in that order, falling back to UTF-8 with invalid bytes replaced
+pub(crate) fn decode(body: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(header_charset)
+        .or_else(|| meta_charset(body))
+        .unwrap_or(UTF_8);
+    // `decode` sniffs the BOM itself and lets it win over the declared charset
+    let (text, used, had_errors) = encoding.decode(body);
+    if had_errors {
+        tracing::debug!(encoding = used.name(), "Body had bytes invalid for its charset, replaced them");
+    }
+    text.into_owned()
+}
+
+/// The `charset` parameter of a `Content-Type` value such as `text/html; charset=Shift_JIS`
+fn header_charset(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("charset") {
+            return None;
+        }
+        Encoding::for_label(value.trim().trim_matches(['"', '\'']).as_bytes())
+    })
+}
+
+/// The charset of a `<meta charset>` or `<meta http-equiv="Content-Type">` near the start of the body
+fn meta_charset(body: &[u8]) -> Option<&'static Encoding> {
+    let head = &body[..body.len().min(META_PRESCAN_BYTES)];
+    let head = String::from_utf8_lossy(head).to_ascii_lowercase();
+    let mut rest = head.as_str();
+    while let Some(start) = rest.find("<meta") {
+        rest = &rest[start + 5..];
+        let tag = &rest[..rest.find('>').unwrap_or(rest.len())];
+        let Some(pos) = tag.find("charset") else {
+            continue;
+        };
+        let Some(value) = tag[pos + 7..].trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start().trim_start_matches(['"', '\'']);
+        let end = value.find(|c: char| c == '"' || c == '\'' || c == ';' || c == '/' || c.is_whitespace()).unwrap_or(value.len());
+        // A page whose <meta> could be read as ASCII is not really UTF-16, so that maps to UTF-8
+        return Encoding::for_label(&value.as_bytes()[..end]).map(|e| e.output_encoding());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// "日本語のページ" encoded as Shift_JIS
+    const SHIFT_JIS_TEXT: &[u8] = b"\x93\xfa\x96\x7b\x8c\xea\x82\xcc\x83\x79\x81\x5b\x83\x57";
+
+    fn page(head: &str) -> Vec<u8> {
+        let mut body = format!("<html><head>{}</head><body><h1>", head).into_bytes();
+        body.extend_from_slice(SHIFT_JIS_TEXT);
+        body.extend_from_slice(b"</h1></body></html>");
+        body
+    }
+
+    #[test]
+    fn shift_jis_from_the_content_type_header() {
+        let html = decode(&page(""), Some("text/html; charset=Shift_JIS"));
+        assert!(html.contains("<h1>日本語のページ</h1>"), "{}", html);
+    }
+
+    #[test]
+    fn shift_jis_from_a_meta_tag() {
+        let html = decode(&page(r#"<meta charset="shift_jis">"#), Some("text/html"));
+        assert!(html.contains("日本語のページ"), "{}", html);
+
+        let html = decode(&page(r#"<meta http-equiv="Content-Type" content="text/html; charset=Shift_JIS">"#), None);
+        assert!(html.contains("日本語のページ"), "{}", html);
+    }
+
+    #[test]
+    fn header_charset_wins_over_the_meta_tag() {
+        let html = decode(&page(r#"<meta charset="utf-8">"#), Some("text/html; charset=\"Shift_JIS\""));
+        assert!(html.contains("日本語のページ"), "{}", html);
+    }
+
+    #[test]
+    fn undeclared_charset_falls_back_to_lossy_utf8() {
+        let html = decode(&page(""), None);
+        assert!(!html.contains("日本語"));
+        assert!(html.contains('\u{FFFD}'));
+        assert_eq!(decode("<p>déjà vu</p>".as_bytes(), None), "<p>déjà vu</p>");
+    }
+}
@@ -1,43 +1,425 @@
 use async_trait::async_trait;
-use rocky_core::{Action, Job, JobError, JobResult, JobWorker, ScrapingAction, ErrorCategory};
-use reqwest::Client;
-use scraper::{Html, Selector};
+use rocky_core::{Action, Axis, FieldSpec, Job, JobError, JobResult, JobWorker, ScrapingAction, ErrorCategory, RecordSink, StreamConfig, Variables, parse_static_selector};
+use reqwest::{Client, Url};
+use scraper::{ElementRef, Html};
 use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+mod charset;
+mod robots;
+
+use robots::RobotsCache;
 
 pub struct ParserWorker {
     client: Client,
+    pool: PoolConfig,
+    accept_invalid_certs: bool,
+    /// Clients for jobs that fetch through a proxy, keyed by proxy URL
+    proxy_clients: Mutex<HashMap<String, Client>>,
+    record_sink: Option<Arc<dyn RecordSink>>,
+    respect_robots: bool,
+    robots: RobotsCache,
+    max_body_bytes: Option<usize>,
+    allowed_content_types: Vec<String>,
+}
+
+/// Largest response body read by default, 10 MiB
+const DEFAULT_MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+/// Connection pool and keep-alive settings for the HTTP client
+///
+/// Requests to one host reuse at most `max_idle_per_host` idle connections; any
+/// concurrency above that opens fresh connections. There is no per-host rate limit
+/// beyond the scheduler's concurrency limit, so for single-host crawls size
+/// `max_idle_per_host` to roughly that limit: lower forces reconnects, higher just
+/// holds sockets the host may close anyway.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub max_idle_per_host: usize,
+    pub idle_timeout: Option<Duration>,
+    pub tcp_keepalive: Option<Duration>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: 32,
+            idle_timeout: Some(Duration::from_secs(90)),
+            tcp_keepalive: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+/// Records from a streaming extract, sent from the parsing thread to be written to the sink
+struct RecordBatch {
+    key: String,
+    records: Vec<serde_json::Value>,
+    delay_ms: u64,
+}
+
+/// Runs a job's actions against the parsed page, off the async runtime
+///
+/// `batches` is set when the worker has a record sink; each send waits for room, so
+/// extraction stays at most a batch ahead of the writes.
+struct ActionRunner {
+    batches: Option<mpsc::Sender<RecordBatch>>,
+}
+
+impl Default for ParserWorker {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ParserWorker {
     pub fn new() -> Self {
-        Self { client: Client::new() }
+        let pool = PoolConfig::default();
+        Self {
+            client: build_client(&pool, false, None),
+            pool,
+            accept_invalid_certs: false,
+            proxy_clients: Mutex::new(HashMap::new()),
+            record_sink: None,
+            respect_robots: false,
+            robots: RobotsCache::new("rocky"),
+            max_body_bytes: Some(DEFAULT_MAX_BODY_BYTES),
+            allowed_content_types: ["text/*", "application/xhtml+xml", "application/xml"].map(String::from).to_vec(),
+        }
+    }
+
+    fn rebuild_client(&mut self) {
+        self.client = build_client(&self.pool, self.accept_invalid_certs, None);
+        self.proxy_clients.lock().unwrap().clear();
+    }
+
+    /// The client to fetch a job with, routed through its proxy if it has one
+    fn client_for(&self, proxy: Option<&str>) -> Result<Client, JobError> {
+        let Some(proxy) = proxy else {
+            return Ok(self.client.clone());
+        };
+        let mut clients = self.proxy_clients.lock().unwrap();
+        if let Some(client) = clients.get(proxy) {
+            return Ok(client.clone());
+        }
+        let parsed = reqwest::Proxy::all(proxy)
+            .map_err(|e| JobError::config_error("proxy", format!("Invalid proxy '{}': {}", proxy, e)))?;
+        let client = build_client(&self.pool, self.accept_invalid_certs, Some(parsed));
+        clients.insert(proxy.to_string(), client.clone());
+        Ok(client)
+    }
+
+    /// Replace all connection pool settings at once
+    pub fn with_pool_config(mut self, pool: PoolConfig) -> Self {
+        self.pool = pool;
+        self.rebuild_client();
+        self
+    }
+
+    /// Accept self-signed, expired or otherwise invalid TLS certificates
+    ///
+    /// This disables certificate verification entirely, so responses can be forged by
+    /// anyone on the network path. Only enable it for internal or staging hosts.
+    pub fn with_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self.rebuild_client();
+        self
+    }
+
+    /// Maximum idle connections kept open per host
+    pub fn with_pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool.max_idle_per_host = max;
+        self.rebuild_client();
+        self
+    }
+
+    /// How long an idle pooled connection is kept; `None` keeps it indefinitely
+    pub fn with_pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pool.idle_timeout = timeout;
+        self.rebuild_client();
+        self
+    }
+
+    /// TCP keep-alive interval for open connections; `None` disables it
+    pub fn with_tcp_keepalive(mut self, interval: Option<Duration>) -> Self {
+        self.pool.tcp_keepalive = interval;
+        self.rebuild_client();
+        self
+    }
+
+    /// Check each job's URL against the host's robots.txt and fail disallowed ones with
+    /// a `Blocked` error; robots.txt is fetched once per origin and cached for an hour
+    pub fn with_respect_robots(mut self, respect: bool) -> Self {
+        self.respect_robots = respect;
+        self
+    }
+
+    /// User agent sent for robots.txt requests and matched against its `User-agent` groups
+    pub fn with_robots_user_agent(mut self, user_agent: &str) -> Self {
+        self.robots.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// How long a host's robots.txt is cached before it is fetched again
+    pub fn with_robots_ttl(mut self, ttl: Duration) -> Self {
+        self.robots.ttl = ttl;
+        self
+    }
+
+    /// Space requests to each host by its robots.txt `Crawl-delay`, if it sets one
+    pub fn with_crawl_delay(mut self, honor: bool) -> Self {
+        self.robots.honor_crawl_delay = honor;
+        self
+    }
+
+    /// Fail jobs whose response body is larger than `max` bytes, without reading past it;
+    /// `None` reads bodies of any size. Defaults to 10 MiB
+    pub fn with_max_body_bytes(mut self, max: Option<usize>) -> Self {
+        self.max_body_bytes = max;
+        self
+    }
+
+    /// Content types the worker parses, as MIME types or `type/*`; responses of any other type
+    /// fail before their body is read. Responses without a `Content-Type` are always parsed
+    ///
+    /// Defaults to `text/*`, `application/xhtml+xml` and `application/xml`.
+    pub fn with_allowed_content_types(mut self, types: Vec<String>) -> Self {
+        self.allowed_content_types = types;
+        self
+    }
+
+    /// Read the response body, failing as soon as it is known to be over `max_body_bytes`
+    async fn read_body(&self, mut response: reqwest::Response, url: &str) -> Result<Vec<u8>, JobError> {
+        let too_large = |limit: usize| {
+            JobError::parsing_error(format!("Response body from {} is larger than {} bytes", url, limit))
+                .with_context(json!({ "url": url, "max_body_bytes": limit }))
+        };
+        if let Some(limit) = self.max_body_bytes
+            && response.content_length().is_some_and(|len| len > limit as u64)
+        {
+            return Err(too_large(limit));
+        }
+
+        // Content-Length can be missing or wrong, so the limit is also checked as chunks arrive
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| JobError::fetch_error(e.to_string()))? {
+            body.extend_from_slice(&chunk);
+            if let Some(limit) = self.max_body_bytes.filter(|limit| body.len() > *limit) {
+                return Err(too_large(limit));
+            }
+        }
+        Ok(body)
+    }
+
+    /// Fail with a `Blocked` error if robots.txt disallows the URL, then wait out any crawl delay
+    async fn check_robots(&self, client: &Client, url: &str) -> Result<(), JobError> {
+        let Ok(url) = Url::parse(url) else {
+            // Let the fetch itself report the bad URL
+            return Ok(());
+        };
+        let rules = self.robots.rules_for(client, &url).await;
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        if !rules.is_allowed(&path) {
+            return Err(JobError::disallowed_by_robots(url.as_str(), &self.robots.user_agent));
+        }
+        if let Some(delay) = rules.crawl_delay.filter(|_| self.robots.honor_crawl_delay) {
+            self.robots.wait_turn(&url, delay).await;
+        }
+        Ok(())
+    }
+
+    /// Sink for extract actions with a `stream` config
+    ///
+    /// The whole document is parsed in memory either way, but records are written batch by
+    /// batch while they are extracted, so only about one batch of them is held at a time.
+    pub fn with_record_sink(mut self, sink: Arc<dyn RecordSink>) -> Self {
+        self.record_sink = Some(sink);
+        self
+    }
+
+    /// Check that a URL is reachable and not an error status, without downloading the body
+    ///
+    /// Sends a HEAD request, falling back to GET for servers that reject HEAD.
+    /// Returns the final status code.
+    pub async fn preflight(&self, url: &str, proxy: Option<&str>) -> Result<u16, JobError> {
+        let client = self.client_for(proxy)?;
+        let mut response = client.head(url).send().await
+            .map_err(|e| JobError::fetch_error(format!("Preflight to {} failed: {}", url, e)))?;
+        if matches!(response.status().as_u16(), 405 | 501) {
+            response = client.get(url).send().await
+                .map_err(|e| JobError::fetch_error(format!("Preflight to {} failed: {}", url, e)))?;
+        }
+        let status = response.status().as_u16();
+        match JobError::from_http_status(status, url) {
+            Some(err) => Err(err),
+            None => Ok(status),
+        }
+    }
+
+    /// Write streamed batches to the sink as they arrive, until the parsing thread is done
+    ///
+    /// Returning drops the receiver, so after a failed write the parsing thread's next send
+    /// fails and it stops extracting.
+    async fn write_batches(&self, job_id: &str, mut batches: mpsc::Receiver<RecordBatch>) -> Result<(), JobError> {
+        let Some(sink) = &self.record_sink else {
+            return Ok(());
+        };
+        while let Some(batch) = batches.recv().await {
+            sink.write_records(job_id, &batch.key, &batch.records).await?;
+            if batch.delay_ms > 0 {
+                tokio::time::sleep(std::time::Duration::from_millis(batch.delay_ms)).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ActionRunner {
+    /// Parse the page and run each action in order, returning the output
+    fn run_actions(&self, html: &str, actions: &[Action], page_url: &Url, collect_partial: bool) -> Result<serde_json::Map<String, serde_json::Value>, JobError> {
+        let document = Html::parse_document(html);
+        let mut output = serde_json::Map::new();
+
+        // Process each action sequentially
+        let mut variables = Variables::new();
+        for (idx, action) in actions.iter().enumerate() {
+            if let Err(mut e) = self.run_action(idx, action, &document, page_url, &mut output, &mut variables) {
+                if collect_partial {
+                    e.add_context("failed_action", serde_json::json!(idx));
+                    e.add_context("partial_output", serde_json::Value::Object(output));
+                }
+                return Err(e);
+            }
+        }
+        Ok(output)
+    }
+
+    /// Put records in the output, or send them to the sink in batches and record a summary
+    fn emit_records(
+        &self,
+        key: String,
+        records: impl Iterator<Item = serde_json::Value>,
+        offset: &Option<usize>,
+        stream: &Option<StreamConfig>,
+        output: &mut serde_json::Map<String, serde_json::Value>,
+    ) -> Result<(), JobError> {
+        match stream {
+            Some(config) => {
+                let Some(batches) = &self.batches else {
+                    return Err(JobError::config_error("record_sink", "Streaming extract requires a record sink on the worker"));
+                };
+                let batch_size = config.batch_size.max(1);
+                let mut count = 0;
+                let mut batch = Vec::with_capacity(batch_size);
+                for record in records {
+                    batch.push(record);
+                    count += 1;
+                    if batch.len() == batch_size {
+                        let records = std::mem::replace(&mut batch, Vec::with_capacity(batch_size));
+                        send_batch(batches, &key, records, config)?;
+                    }
+                }
+                if !batch.is_empty() {
+                    send_batch(batches, &key, batch, config)?;
+                }
+                output.insert(key, json!({
+                    "streamed": count,
+                    "next_offset": offset.unwrap_or(0) + count,
+                }));
+            }
+            None => {
+                output.insert(key, serde_json::Value::Array(records.collect()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Run one action against the parsed page
+    /// `index` is the job-level position, used to record the error of a failed `Optional` action
+    ///
+    /// Variables are substituted inside `Optional`, so an unresolved one only skips that action.
+    fn run_action(
+        &self,
+        index: usize,
+        action: &Action,
+        document: &Html,
+        page_url: &Url,
+        output: &mut serde_json::Map<String, serde_json::Value>,
+        variables: &mut Variables,
+    ) -> Result<(), JobError> {
+        match action {
+            Action::Scraping(_) => {
+                let action = variables.substitute(action)?;
+                if let Action::Scraping(scraping_action) = &action {
+                    self.handle_scraping_action(scraping_action, document, page_url, output)?;
+                }
+                variables.capture(&action, output);
+                Ok(())
+            }
+            Action::Optional(inner) => {
+                if let Err(e) = self.run_action(index, inner, document, page_url, output, variables) {
+                    tracing::warn!(action_index = index, error = %e, "Optional action failed, continuing");
+                    output.insert(format!("error:{}", index), serde_json::json!(e));
+                }
+                Ok(())
+            }
+            Action::Browser(_) => Err(JobError::new(
+                ErrorCategory::Unknown,
+                "ParserWorker cannot execute browser actions. Use BrowserWorker instead."
+            )),
+        }
     }
 
     fn handle_scraping_action(
         &self,
         action: &ScrapingAction,
         document: &Html,
+        page_url: &Url,
         output: &mut serde_json::Map<String, serde_json::Value>,
     ) -> Result<(), JobError> {
         match action {
-            ScrapingAction::Fetch { .. } => {
-                // Fetch is handled at the job level, not per action
+            ScrapingAction::Fetch { .. } | ScrapingAction::Request { .. } => {
+                // Fetch and Request are handled at the job level, not per action
             }
             ScrapingAction::WaitFor { selector, .. } => {
                 // For static HTML parsing, we just check if the element exists
-                let sel = Selector::parse(selector)
-                    .map_err(|e| JobError::parsing_error(e.to_string()))?;
+                let sel = parse_static_selector(selector)?;
                 let found = document.select(&sel).next().is_some();
                 output.insert(format!("waitfor:{}", selector), json!(found));
             }
-            ScrapingAction::Extract { selector, attr } => {
-                let sel = Selector::parse(selector)
-                    .map_err(|e| JobError::parsing_error(e.to_string()))?;
+            ScrapingAction::AssertAbsent { selector } => {
+                let sel = parse_static_selector(selector)?;
+                let count = document.select(&sel).count();
+                if count > 0 {
+                    return Err(JobError::unexpected_element(selector, count));
+                }
+                output.insert(format!("assert_absent:{}", selector), json!(true));
+            }
+            ScrapingAction::AssertCount { selector, min, max } => {
+                let sel = parse_static_selector(selector)?;
+                let count = document.select(&sel).count();
+                output.insert(format!("count:{}", selector), json!(count));
+                JobError::check_count(selector, count, *min, *max)?;
+            }
+            ScrapingAction::Extract { selector, attr, limit, offset, resolve_urls, .. } => {
+                let sel = parse_static_selector(selector)?;
+                let base = if *resolve_urls { Some(base_url(document, page_url)?) } else { None };
                 let values: Vec<String> = document
                     .select(&sel)
+                    .skip(offset.unwrap_or(0))
+                    .take(limit.unwrap_or(usize::MAX))
                     .map(|el| {
                         if let Some(a) = attr {
-                            el.value().attr(a).unwrap_or("").to_string()
+                            let value = el.value().attr(a).unwrap_or("");
+                            match &base {
+                                Some(base) if is_url_attr(a) => resolve_url(base, value),
+                                _ => value.to_string(),
+                            }
                         } else {
                             el.text().collect::<Vec<_>>().join("")
                         }
@@ -45,66 +427,609 @@ impl ParserWorker {
                     .collect();
                 output.insert(format!("extract:{}", selector), json!(values));
             }
-            ScrapingAction::ExtractMultiple { selector, attrs } => {
-                let sel = Selector::parse(selector)
-                    .map_err(|e| JobError::parsing_error(e.to_string()))?;
+            ScrapingAction::ExtractOne { selector, attr, default, resolve_urls, .. } => {
+                let sel = parse_static_selector(selector)?;
+                let base = if *resolve_urls { Some(base_url(document, page_url)?) } else { None };
+                let value = document.select(&sel).next().map(|el| match attr {
+                    Some(a) => {
+                        let value = el.value().attr(a).unwrap_or("");
+                        match &base {
+                            Some(base) if is_url_attr(a) => resolve_url(base, value),
+                            _ => value.to_string(),
+                        }
+                    }
+                    None => el.text().collect::<Vec<_>>().join(""),
+                });
+                output.insert(format!("extract_one:{}", selector), json!(value.or_else(|| default.clone())));
+            }
+            ScrapingAction::ExtractInlineState { var_name, path } => {
+                let state = extract_inline_state(document, var_name)?;
+                let value = match (state, path) {
+                    (Some(state), Some(path)) => {
+                        // Escaped per RFC 6901 so keys containing `~` or `/` are matched literally
+                        let pointer: String = path
+                            .split('.')
+                            .map(|key| format!("/{}", key.replace('~', "~0").replace('/', "~1")))
+                            .collect();
+                        state.pointer(&pointer).cloned().unwrap_or(serde_json::Value::Null)
+                    }
+                    (Some(state), None) => state,
+                    (None, _) => serde_json::Value::Null,
+                };
+                output.insert(format!("inline_state:{}", var_name), value);
+            }
+            ScrapingAction::ExtractTable { selector, header } => {
+                let sel = parse_static_selector(selector)?;
+                let rows = match document.select(&sel).next() {
+                    Some(table) => extract_table(table, *header),
+                    None => Vec::new(),
+                };
+                output.insert(format!("extract_table:{}", selector), json!(rows));
+            }
+            ScrapingAction::ExtractStructuredData { types } => {
+                let sel = parse_static_selector(r#"script[type="application/ld+json"]"#)?;
+                let blocks: Vec<String> = document.select(&sel).map(|s| s.text().collect()).collect();
+                output.insert("structured_data".to_string(), json!(structured_data(&blocks, types)));
+            }
+            ScrapingAction::ExtractDataAttrs { selector } => {
+                let sel = parse_static_selector(selector)?;
                 let results: Vec<serde_json::Value> = document
                     .select(&sel)
+                    .map(|el| {
+                        let mut obj = serde_json::Map::new();
+                        for (name, value) in el.value().attrs() {
+                            if let Some(key) = name.strip_prefix("data-") {
+                                obj.insert(dataset_key(key), json!(value));
+                            }
+                        }
+                        obj.insert("text".to_string(), json!(element_value(&el, None)));
+                        serde_json::Value::Object(obj)
+                    })
+                    .collect();
+                output.insert(format!("data_attrs:{}", selector), json!(results));
+            }
+            ScrapingAction::ExtractFirst { selectors, attr, .. } => {
+                let sels = selectors
+                    .iter()
+                    .map(|s| parse_static_selector(s))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let mut result = json!({ "selector": null, "values": [] });
+                for (selector, sel) in selectors.iter().zip(&sels) {
+                    let values: Vec<String> = document
+                        .select(sel)
+                        .map(|el| match attr {
+                            Some(a) => el.value().attr(a).unwrap_or("").to_string(),
+                            None => el.text().collect::<Vec<_>>().join("").trim().to_string(),
+                        })
+                        .collect();
+                    if !values.is_empty() {
+                        result = json!({ "selector": selector, "values": values });
+                        break;
+                    }
+                }
+                output.insert(format!("extract_first:{}", selectors.join(", ")), result);
+            }
+            ScrapingAction::ExtractMultiple { selector, attrs, limit, offset, resolve_urls, stream, .. } => {
+                let sel = parse_static_selector(selector)?;
+                let base = if *resolve_urls { Some(base_url(document, page_url)?) } else { None };
+                let results = document
+                    .select(&sel)
+                    .skip(offset.unwrap_or(0))
+                    .take(limit.unwrap_or(usize::MAX))
                     .map(|el| {
                         let mut obj = serde_json::Map::new();
                         for attr in attrs {
                             let value = if attr == "text" {
                                 el.text().collect::<Vec<_>>().join("")
                             } else {
-                                el.value().attr(attr).unwrap_or("").to_string()
+                                let value = el.value().attr(attr).unwrap_or("");
+                                match &base {
+                                    Some(base) if is_url_attr(attr) => resolve_url(base, value),
+                                    _ => value.to_string(),
+                                }
                             };
                             obj.insert(attr.clone(), json!(value));
                         }
                         serde_json::Value::Object(obj)
+                    });
+                self.emit_records(format!("extract_multiple:{}", selector), results, offset, stream, output)?;
+            }
+            ScrapingAction::ExtractMeta => {
+                output.insert("meta".to_string(), extract_meta(document)?);
+            }
+            ScrapingAction::ExtractAnchors { selector } => {
+                output.insert("anchors".to_string(), extract_anchors(document, page_url, selector.as_deref())?);
+            }
+            ScrapingAction::ExtractItems { container, fields, limit, offset, stream } => {
+                let container_sel = parse_static_selector(container)?;
+                let field_sels = fields
+                    .iter()
+                    .map(|f| {
+                        f.selector
+                            .as_deref()
+                            .map(parse_static_selector)
+                            .transpose()
                     })
-                    .collect();
-                output.insert(format!("extract_multiple:{}", selector), json!(results));
+                    .collect::<Result<Vec<_>, _>>()?;
+                let closest_sels = fields
+                    .iter()
+                    .map(|f| match &f.axis {
+                        Some(Axis::Closest(selector)) => parse_static_selector(selector).map(Some),
+                        _ => Ok(None),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let items = document
+                    .select(&container_sel)
+                    .skip(offset.unwrap_or(0))
+                    .take(limit.unwrap_or(usize::MAX))
+                    .map(|item| {
+                        let mut obj = serde_json::Map::new();
+                        for ((field, sel), closest) in fields.iter().zip(&field_sels).zip(&closest_sels) {
+                            let matches: Vec<ElementRef> = match (resolve_axis(item, field, closest.as_ref()), sel) {
+                                (Some(base), Some(sel)) => base.select(sel).collect(),
+                                (Some(base), None) => vec![base],
+                                (None, _) => Vec::new(),
+                            };
+                            let attr = field.attr.as_deref();
+                            let value = if field.multiple {
+                                json!(matches.iter().map(|el| element_value(el, attr)).collect::<Vec<_>>())
+                            } else {
+                                matches.first().map_or(serde_json::Value::Null, |el| json!(element_value(el, attr)))
+                            };
+                            obj.insert(field.name.clone(), value);
+                        }
+                        serde_json::Value::Object(obj)
+                    });
+                self.emit_records(format!("extract_items:{}", container), items, offset, stream, output)?;
             }
         }
         Ok(())
     }
 }
 
+/// Hand a batch to the sink writer, waiting while the previous one is still being written
+fn send_batch(batches: &mpsc::Sender<RecordBatch>, key: &str, records: Vec<serde_json::Value>, config: &StreamConfig) -> Result<(), JobError> {
+    let batch = RecordBatch { key: key.to_string(), records, delay_ms: config.delay_ms };
+    batches.blocking_send(batch)
+        .map_err(|_| JobError::new(ErrorCategory::Unknown, format!("Record sink for {} stopped before all records were written", key)))
+}
+
+fn build_client(pool: &PoolConfig, accept_invalid_certs: bool, proxy: Option<reqwest::Proxy>) -> Client {
+    let mut builder = Client::builder()
+        .pool_max_idle_per_host(pool.max_idle_per_host)
+        .pool_idle_timeout(pool.idle_timeout)
+        .tcp_keepalive(pool.tcp_keepalive)
+        .danger_accept_invalid_certs(accept_invalid_certs);
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(proxy);
+    }
+    builder.build().expect("Failed to build HTTP client")
+}
+
+/// The request for a job's URL: a GET, or the method and body of its first `Request` action
+fn build_request(client: &Client, job: &Job) -> Result<reqwest::RequestBuilder, JobError> {
+    let spec = job.actions.iter().find_map(|action| match action {
+        Action::Scraping(ScrapingAction::Request { method, body, content_type }) => Some((method, body, content_type)),
+        _ => None,
+    });
+    let Some((method, body, content_type)) = spec else {
+        return Ok(client.get(&job.url));
+    };
+
+    let method = reqwest::Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+        .map_err(|_| JobError::config_error("method", format!("Invalid HTTP method '{}'", method)))?;
+    let mut request = client.request(method, &job.url);
+    let is_form = content_type.as_deref().is_some_and(|c| c.starts_with("application/x-www-form-urlencoded"));
+    request = match body {
+        None => request,
+        Some(serde_json::Value::String(raw)) => request.body(raw.clone()),
+        Some(serde_json::Value::Object(fields)) if is_form => {
+            let fields: Vec<(&String, String)> = fields
+                .iter()
+                .map(|(k, v)| (k, v.as_str().map_or_else(|| v.to_string(), str::to_string)))
+                .collect();
+            request.form(&fields)
+        }
+        Some(body) => request.json(body),
+    };
+    // Set last so an explicit content type wins over the one implied by the body
+    if let Some(content_type) = content_type {
+        request = request.header(reqwest::header::CONTENT_TYPE, content_type);
+    }
+    Ok(request)
+}
+
+/// Title, description, canonical URL and social tags of a document
+fn extract_meta(document: &Html) -> Result<serde_json::Value, JobError> {
+    let first = |selector: &str, attr: Option<&str>| -> Result<serde_json::Value, JobError> {
+        let sel = parse_static_selector(selector)?;
+        Ok(document
+            .select(&sel)
+            .next()
+            .map_or(serde_json::Value::Null, |el| json!(element_value(&el, attr))))
+    };
+    let tags = |prefix: &str| -> Result<serde_json::Value, JobError> {
+        let sel = parse_static_selector("meta[property], meta[name]")?;
+        let mut map = serde_json::Map::new();
+        for el in document.select(&sel) {
+            let key = el.value().attr("property").or_else(|| el.value().attr("name")).unwrap_or("");
+            if key.starts_with(prefix) {
+                map.insert(key.to_string(), json!(el.value().attr("content").unwrap_or("")));
+            }
+        }
+        Ok(serde_json::Value::Object(map))
+    };
+
+    Ok(json!({
+        "title": first("title", None)?,
+        "description": first("meta[name=\"description\"]", Some("content"))?,
+        "canonical": first("link[rel=\"canonical\"]", Some("href"))?,
+        "open_graph": tags("og:")?,
+        "twitter": tags("twitter:")?,
+    }))
+}
+
+/// Every link matched by `selector` with its href resolved against the document base
+fn extract_anchors(document: &Html, page_url: &Url, selector: Option<&str>) -> Result<serde_json::Value, JobError> {
+    let base = base_url(document, page_url)?;
+
+    let sel = parse_static_selector(selector.unwrap_or("a[href]"))?;
+    let anchors: Vec<serde_json::Value> = document
+        .select(&sel)
+        .filter_map(|el| {
+            let href = el.value().attr("href")?;
+            Some(json!({
+                "href_absolute": base.join(href.trim()).ok().map(String::from),
+                "text": element_value(&el, None),
+                "rel": el.value().attr("rel"),
+                "target": el.value().attr("target"),
+            }))
+        })
+        .collect();
+    Ok(json!(anchors))
+}
+
+/// Rows of a table as column name -> cell text objects
+fn extract_table(table: ElementRef, header: bool) -> Vec<serde_json::Value> {
+    let is = |el: &ElementRef, names: &[&str]| names.contains(&el.value().name());
+    // Rows directly in the table or its sections, so nested tables are left out
+    let rows: Vec<Vec<String>> = table
+        .children()
+        .filter_map(ElementRef::wrap)
+        .flat_map(|child| {
+            if is(&child, &["thead", "tbody", "tfoot"]) {
+                child.children().filter_map(ElementRef::wrap).filter(|r| is(r, &["tr"])).collect::<Vec<_>>()
+            } else if is(&child, &["tr"]) {
+                vec![child]
+            } else {
+                Vec::new()
+            }
+        })
+        .map(|row| {
+            row.children()
+                .filter_map(ElementRef::wrap)
+                .filter(|cell| is(cell, &["td", "th"]))
+                .flat_map(|cell| {
+                    let span = cell.value().attr("colspan").and_then(|s| s.trim().parse::<usize>().ok()).unwrap_or(1).clamp(1, 1000);
+                    std::iter::repeat_n(element_value(&cell, None), span)
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut rows = rows.into_iter();
+    let mut columns: Vec<String> = Vec::new();
+    if header {
+        for (i, name) in rows.next().unwrap_or_default().into_iter().enumerate() {
+            let base = if name.is_empty() { i.to_string() } else { name };
+            let mut unique = base.clone();
+            let mut n = 2;
+            while columns.contains(&unique) {
+                unique = format!("{}_{}", base, n);
+                n += 1;
+            }
+            columns.push(unique);
+        }
+    }
+
+    rows.map(|cells| {
+        let width = cells.len().max(columns.len());
+        let mut obj = serde_json::Map::new();
+        for i in 0..width {
+            let key = columns.get(i).cloned().unwrap_or_else(|| i.to_string());
+            obj.insert(key, json!(cells.get(i).cloned().unwrap_or_default()));
+        }
+        serde_json::Value::Object(obj)
+    })
+    .collect()
+}
+
+/// URL relative links resolve against: the page URL, unless a `<base href>` overrides it
+fn base_url(document: &Html, page_url: &Url) -> Result<Url, JobError> {
+    let base_sel = parse_static_selector("base[href]")?;
+    Ok(document
+        .select(&base_sel)
+        .next()
+        .and_then(|el| el.value().attr("href"))
+        .and_then(|href| page_url.join(href).ok())
+        .unwrap_or_else(|| page_url.clone()))
+}
+
+fn is_url_attr(attr: &str) -> bool {
+    attr == "href" || attr == "src"
+}
+
+/// Absolute form of a URL attribute; empty and `javascript:` values pass through unchanged
+fn resolve_url(base: &Url, value: &str) -> String {
+    let trimmed = value.trim();
+    if trimmed.is_empty() || trimmed.get(..11).is_some_and(|s| s.eq_ignore_ascii_case("javascript:")) {
+        return value.to_string();
+    }
+    base.join(trimmed).map(String::from).unwrap_or_else(|_| value.to_string())
+}
+
+/// The element a field is read from: the container, or the relative its `axis` names
+fn resolve_axis<'a>(item: ElementRef<'a>, field: &FieldSpec, closest: Option<&scraper::Selector>) -> Option<ElementRef<'a>> {
+    match &field.axis {
+        None => Some(item),
+        Some(Axis::Parent) => item.parent().and_then(ElementRef::wrap),
+        Some(Axis::NextSibling) => item.next_siblings().find_map(ElementRef::wrap),
+        Some(Axis::PreviousSibling) => item.prev_siblings().find_map(ElementRef::wrap),
+        Some(Axis::Closest(_)) => {
+            let sel = closest?;
+            std::iter::once(item)
+                .chain(item.ancestors().filter_map(ElementRef::wrap))
+                .find(|el| sel.matches(el))
+        }
+    }
+}
+
+/// Find `var_name = <value>` in an inline script and parse the value
+/// Handles `window.X =`, `window["X"] =`, `var/let/const X =` and `JSON.parse("...")` values
+fn extract_inline_state(document: &Html, var_name: &str) -> Result<Option<serde_json::Value>, JobError> {
+    // An empty name matches everywhere without advancing the search
+    if var_name.trim().is_empty() {
+        return Err(JobError::config_error("var_name", "ExtractInlineState needs a non-empty var_name")
+            .with_context(json!({ "config_field": "var_name" })));
+    }
+    let sel = parse_static_selector("script:not([src])")?;
+    for script in document.select(&sel) {
+        let source = script.text().collect::<String>();
+        let mut search_from = 0;
+        while let Some(pos) = source[search_from..].find(var_name) {
+            let start = search_from + pos;
+            let end = start + var_name.len();
+            search_from = end;
+
+            // Must be the whole identifier, not a prefix or suffix of a longer one
+            let before = source[..start].chars().next_back();
+            let after = source[end..].chars().next();
+            if before.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '$')
+                || after.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '$')
+            {
+                continue;
+            }
+            let rest = source[end..].trim_start_matches(['"', '\'', ']']).trim_start();
+            let Some(rest) = rest.strip_prefix('=').filter(|r| !r.starts_with('=')) else {
+                continue;
+            };
+            return parse_state_value(rest.trim_start(), var_name).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// Parse JSON-LD blocks into their items, flattening top-level arrays and `@graph`,
+/// keeping those whose `@type` is one of `types` (all when empty)
+///
+/// Shared with the browser worker so both filter JSON-LD the same way.
+pub fn structured_data(blocks: &[String], types: &[String]) -> Vec<serde_json::Value> {
+    let mut items = Vec::new();
+    for (index, block) in blocks.iter().enumerate() {
+        let value: serde_json::Value = match serde_json::from_str(block.trim()) {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::warn!(index, error = %e, "Skipping malformed JSON-LD block");
+                continue;
+            }
+        };
+        let roots = match value {
+            serde_json::Value::Array(values) => values,
+            value => vec![value],
+        };
+        for root in roots {
+            match root.get("@graph").and_then(|g| g.as_array()) {
+                Some(graph) => items.extend(graph.iter().cloned()),
+                None => items.push(root),
+            }
+        }
+    }
+    if types.is_empty() {
+        return items;
+    }
+    items
+        .into_iter()
+        .filter(|item| match item.get("@type") {
+            Some(serde_json::Value::String(t)) => types.contains(t),
+            Some(serde_json::Value::Array(ts)) => ts.iter().filter_map(|t| t.as_str()).any(|t| types.iter().any(|w| w == t)),
+            _ => false,
+        })
+        .collect()
+}
+
+/// Parse the first JSON value at the start of `source`, unwrapping `JSON.parse("...")`
+fn parse_state_value(source: &str, var_name: &str) -> Result<serde_json::Value, JobError> {
+    let parse_error = |e: serde_json::Error| {
+        JobError::parsing_error(format!("Inline state '{}' is not valid JSON: {}", var_name, e))
+            .with_context(json!({ "var_name": var_name, "hint": "Run this job with use_browser: true" }))
+    };
+    let first_value = |s: &str| {
+        serde_json::Deserializer::from_str(s)
+            .into_iter::<serde_json::Value>()
+            .next()
+            .unwrap_or_else(|| serde_json::from_str(""))
+    };
+
+    match source.strip_prefix("JSON.parse(") {
+        Some(inner) => {
+            let encoded = first_value(inner.trim_start()).map_err(parse_error)?;
+            let text = encoded.as_str().ok_or_else(|| {
+                JobError::parsing_error(format!("Inline state '{}' passes a non-string to JSON.parse", var_name))
+            })?;
+            serde_json::from_str(text).map_err(parse_error)
+        }
+        None => first_value(source).map_err(parse_error),
+    }
+}
+
+/// CamelCase a `data-` attribute suffix the way `el.dataset` does: `foo-bar` becomes `fooBar`
+fn dataset_key(name: &str) -> String {
+    let mut key = String::with_capacity(name.len());
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(next) if c == '-' && next.is_ascii_lowercase() => {
+                key.push(next.to_ascii_uppercase());
+                chars.next();
+            }
+            _ => key.push(c),
+        }
+    }
+    key
+}
+
+/// Whether the MIME type of a `Content-Type` value matches one of `allowed`, which may use `type/*`
+fn content_type_allowed(content_type: &str, allowed: &[String]) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    allowed.iter().any(|a| match a.strip_suffix("/*") {
+        Some(top) => mime.split('/').next().is_some_and(|t| t.eq_ignore_ascii_case(top)),
+        None => mime.eq_ignore_ascii_case(a),
+    })
+}
+
+/// Read a field value from an element: text by default, inner HTML, or an attribute
+fn element_value(el: &ElementRef, attr: Option<&str>) -> String {
+    match attr {
+        None | Some("text") => el.text().collect::<Vec<_>>().join("").trim().to_string(),
+        Some("html") => el.inner_html(),
+        Some(a) => el.value().attr(a).unwrap_or("").to_string(),
+    }
+}
+
 #[async_trait]
 impl JobWorker for ParserWorker {
+    #[tracing::instrument(name = "parser_job", skip_all, fields(job_id = %job.id))]
     async fn execute(&self, job: &Job) -> Result<JobResult, JobError> {
+        let Some(timeout_ms) = job.timeout_ms else {
+            return self.run(job).await;
+        };
+        let started = std::time::Instant::now();
+        // A timeout while parsing drops the batch receiver, so the parsing thread stops at its next send
+        tokio::time::timeout(std::time::Duration::from_millis(timeout_ms), self.run(job)).await
+            .unwrap_or_else(|_| Err(JobError::job_timeout(timeout_ms, started.elapsed().as_millis() as u64, None)))
+    }
+}
+
+impl ParserWorker {
+    /// The job from fetch to result, without the overall timeout
+    async fn run(&self, job: &Job) -> Result<JobResult, JobError> {
+        job.validate()?;
+
         // Fetch page
-        let html = self.client
-            .get(&job.url)
+        let client = self.client_for(job.proxy.as_deref())?;
+        if self.respect_robots {
+            self.check_robots(&client, &job.url).await?;
+        }
+        let mut request = build_request(&client, job)?;
+        if let Some(referer) = &job.referer {
+            request = request.header(reqwest::header::REFERER, referer);
+        }
+        let response = request
             .send()
             .await
-            .map_err(|e| JobError::fetch_error(e.to_string()))?
-            .text()
-            .await
             .map_err(|e| JobError::fetch_error(e.to_string()))?;
-
-        let document = Html::parse_document(&html);
-        let mut output = serde_json::Map::new();
-
-        // Process each action sequentially
-        for action in &job.actions {
-            match action {
-                Action::Scraping(scraping_action) => {
-                    self.handle_scraping_action(scraping_action, &document, &mut output)?;
-                }
-                Action::Browser(_) => {
-                    return Err(JobError::new(
-                        ErrorCategory::Unknown,
-                        "ParserWorker cannot execute browser actions. Use BrowserWorker instead."
-                    ));
-                }
-            }
+        // Relative links resolve against the final URL, after redirects
+        let page_url = response.url().clone();
+        if !response.status().is_success() {
+            // Redirects are followed, so a 3xx that gets here (such as 304) has no page to parse
+            let status = response.status().as_u16();
+            return Err(JobError::from_http_status(status, page_url.as_str())
+                .unwrap_or_else(|| JobError::navigation_error(format!("{} returned HTTP {}", page_url, status))));
         }
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        if let Some(content_type) = content_type.as_deref().filter(|c| !content_type_allowed(c, &self.allowed_content_types)) {
+            return Err(JobError::parsing_error(format!("{} has content type {}, which is not parsed", page_url, content_type))
+                .with_context(json!({
+                    "url": page_url.as_str(),
+                    "content_type": content_type,
+                    "allowed_content_types": self.allowed_content_types,
+                })));
+        }
+        let body = self.read_body(response, page_url.as_str()).await?;
+        let html = charset::decode(&body, content_type.as_deref());
+
+        // Parsing is CPU-bound and the document is not Send, so the actions run on a blocking
+        // thread while streamed batches are written here
+        let (sender, receiver) = mpsc::channel(1);
+        let runner = ActionRunner { batches: self.record_sink.is_some().then_some(sender) };
+        let actions = job.actions.clone();
+        let collect_partial = job.collect_partial;
+        let parsing = tokio::task::spawn_blocking(move || runner.run_actions(&html, &actions, &page_url, collect_partial));
+        let written = self.write_batches(&job.id, receiver).await;
+        let output = parsing.await
+            .map_err(|e| JobError::new(ErrorCategory::Unknown, format!("Parsing task failed: {}", e)))?;
+        // A failed write also fails the extraction, so the sink's error is the one to report
+        written?;
+        let output = output?;
 
         Ok(JobResult {
             job_id: job.id.clone(),
             success: true,
             output: serde_json::Value::Object(output),
+            tags: job.tags.clone(),
+            error: None,
         })
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn actions(actions: serde_json::Value) -> Vec<Action> {
+        serde_json::from_value(actions).unwrap()
+    }
+
+    fn run(actions: &[Action]) -> Result<serde_json::Map<String, serde_json::Value>, JobError> {
+        let html = "<html><body><h1>Title</h1><p class='price'>10</p></body></html>";
+        let url = Url::parse("https://example.com/").unwrap();
+        ActionRunner { batches: None }.run_actions(html, actions, &url, true)
+    }
+
+    #[test]
+    fn optional_action_with_an_unresolved_variable_is_skipped() {
+        let actions = actions(json!([
+            { "Optional": { "Scraping": { "Extract": { "selector": "${missing}", "attr": null } } } },
+            { "Scraping": { "Extract": { "selector": "h1", "attr": null } } },
+        ]));
+        let output = run(&actions).unwrap();
+        assert_eq!(output["error:0"]["category"], "Config");
+        assert_eq!(output["extract:h1"], json!(["Title"]));
+    }
+
+    #[test]
+    fn unresolved_variable_outside_optional_fails_with_partial_output() {
+        let actions = actions(json!([
+            { "Scraping": { "Extract": { "selector": "h1", "attr": null } } },
+            { "Scraping": { "Extract": { "selector": "${missing}", "attr": null } } },
+        ]));
+        let error = run(&actions).unwrap_err();
+        assert_eq!(error.context["failed_action"], 1);
+        assert_eq!(error.context["partial_output"]["extract:h1"], json!(["Title"]));
+    }
+}
@@ -0,0 +1,175 @@
+use reqwest::{Client, Url};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Allow and disallow rules from one host's robots.txt that apply to our user agent
+#[derive(Debug, Default)]
+pub(crate) struct RobotsRules {
+    /// `(allow, pattern)` pairs
+    rules: Vec<(bool, String)>,
+    pub(crate) crawl_delay: Option<Duration>,
+}
+
+impl RobotsRules {
+    /// Parse robots.txt, keeping the groups for `user_agent` or, if none name it, the `*` groups
+    pub(crate) fn parse(body: &str, user_agent: &str) -> Self {
+        let user_agent = user_agent.to_ascii_lowercase();
+        let mut specific = RobotsRules::default();
+        let mut wildcard = RobotsRules::default();
+        let mut has_specific = false;
+
+        // Agents of the group being read, and whether its rules have started
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+
+        for line in body.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((field, value)) = line.split_once(':') else {
+                continue;
+            };
+            let field = field.trim().to_ascii_lowercase();
+            let value = value.trim();
+
+            if field == "user-agent" {
+                if in_rules {
+                    agents.clear();
+                    in_rules = false;
+                }
+                agents.push(value.to_ascii_lowercase());
+                continue;
+            }
+            in_rules = true;
+            let matches_us = agents.iter().any(|a| a != "*" && user_agent.contains(a.as_str()));
+            let matches_any = agents.iter().any(|a| a == "*");
+            has_specific |= matches_us;
+
+            let target = if matches_us {
+                Some(&mut specific)
+            } else if matches_any {
+                Some(&mut wildcard)
+            } else {
+                None
+            };
+            if let Some(rules) = target {
+                match field.as_str() {
+                    "allow" if !value.is_empty() => rules.rules.push((true, value.to_string())),
+                    // An empty Disallow allows everything, so it adds no rule
+                    "disallow" if !value.is_empty() => rules.rules.push((false, value.to_string())),
+                    "crawl-delay" => {
+                        if let Ok(secs) = value.parse::<f64>()
+                            && secs.is_finite() && secs >= 0.0
+                        {
+                            rules.crawl_delay = Some(Duration::from_secs_f64(secs));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if has_specific { specific } else { wildcard }
+    }
+
+    /// Whether `path` (with its query) may be fetched: the longest matching rule wins, allow on ties
+    pub(crate) fn is_allowed(&self, path: &str) -> bool {
+        let mut best: Option<(usize, bool)> = None;
+        for (allow, pattern) in &self.rules {
+            if !pattern_matches(pattern, path) {
+                continue;
+            }
+            let len = pattern.len();
+            best = match best {
+                Some((best_len, best_allow)) if best_len > len || (best_len == len && best_allow) => Some((best_len, best_allow)),
+                _ => Some((len, *allow)),
+            };
+        }
+        best.is_none_or(|(_, allow)| allow)
+    }
+}
+
+/// Match a robots.txt path pattern, where `*` matches any run of characters and a trailing `$` anchors the end
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let Some(rest) = path.strip_prefix(parts[0]) else {
+        return false;
+    };
+    if parts.len() == 1 {
+        return !anchored || rest.is_empty();
+    }
+
+    let mut rest = rest;
+    for (i, part) in parts[1..].iter().enumerate() {
+        let last = i == parts.len() - 2;
+        if last && anchored {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// robots.txt rules per host, refetched once older than `ttl`, plus the last fetch time per host
+/// for spacing requests by `Crawl-delay`
+pub(crate) struct RobotsCache {
+    pub(crate) user_agent: String,
+    pub(crate) ttl: Duration,
+    pub(crate) honor_crawl_delay: bool,
+    entries: Mutex<HashMap<String, (Instant, Arc<RobotsRules>)>>,
+    next_fetch: Mutex<HashMap<String, Instant>>,
+}
+
+impl RobotsCache {
+    pub(crate) fn new(user_agent: &str) -> Self {
+        Self {
+            user_agent: user_agent.to_string(),
+            ttl: Duration::from_secs(3600),
+            honor_crawl_delay: false,
+            entries: Mutex::new(HashMap::new()),
+            next_fetch: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Rules for the URL's origin, fetching robots.txt if not cached or stale
+    ///
+    /// A robots.txt that is missing, unreadable or returns an error status allows everything.
+    pub(crate) async fn rules_for(&self, client: &Client, url: &Url) -> Arc<RobotsRules> {
+        let origin = url.origin().ascii_serialization();
+        if let Some((fetched, rules)) = self.entries.lock().unwrap().get(&origin)
+            && fetched.elapsed() < self.ttl
+        {
+            return Arc::clone(rules);
+        }
+
+        let robots_url = format!("{}/robots.txt", origin);
+        let body = match client.get(&robots_url).header(reqwest::header::USER_AGENT, &self.user_agent).send().await {
+            Ok(response) if response.status().is_success() => response.text().await.unwrap_or_default(),
+            Ok(_) | Err(_) => String::new(),
+        };
+        let rules = Arc::new(RobotsRules::parse(&body, &self.user_agent));
+        self.entries.lock().unwrap().insert(origin, (Instant::now(), Arc::clone(&rules)));
+        rules
+    }
+
+    /// Wait until `delay` has passed since the previous request to this URL's origin
+    pub(crate) async fn wait_turn(&self, url: &Url, delay: Duration) {
+        let origin = url.origin().ascii_serialization();
+        let wait = {
+            let mut next_fetch = self.next_fetch.lock().unwrap();
+            let now = Instant::now();
+            let slot = next_fetch.get(&origin).copied().filter(|t| *t > now).unwrap_or(now);
+            next_fetch.insert(origin, slot + delay);
+            slot - now
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
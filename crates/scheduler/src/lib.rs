@@ -1,19 +1,79 @@
-use rocky_core::{Job, JobWorker, ErrorHealer, ErrorContext, HealingAction, DefaultErrorHealer};
-use rocky_storage::Storage;
+use rocky_core::{BrowserType, Job, JobError, JobResult, JobWorker, ErrorCategory, ErrorHealer, ErrorContext, HealingAction, DefaultErrorHealer};
+use rocky_storage::{Storage, JobQueue, JobStatus};
 use futures::stream::{FuturesUnordered, StreamExt};
+use rand::Rng;
 use std::sync::Arc;
-use std::collections::HashMap;
-use tokio::sync::{mpsc, Semaphore, Mutex};
+use std::collections::{HashMap, HashSet};
+use tokio::sync::{mpsc, watch, Semaphore, Mutex};
+use tracing::{error, info, warn};
+
+mod metrics;
+mod pending;
+mod proxies;
+mod report;
+mod resources;
+
+pub use metrics::{Metrics, NoopMetrics};
+#[cfg(feature = "prometheus")]
+pub use metrics::PrometheusMetrics;
+pub use report::BatchReport;
+pub use resources::{ResourceLimits, browser_memory_mb};
+use pending::PendingJobs;
+use proxies::ProxyPool;
+use resources::BrowserGuard;
 
 pub struct Scheduler<S: Storage + 'static> {
     parser_worker: Arc<dyn JobWorker>,
     browser_worker: Arc<dyn JobWorker>,
+    /// Browser jobs whose `browser_type` is `Firefox`, when set
+    firefox_worker: Option<Arc<dyn JobWorker>>,
     storage: Arc<S>,
     sender: mpsc::Sender<Job>,
     concurrency_limit: Arc<Semaphore>,
     error_healer: Arc<dyn ErrorHealer>,
     retry_counts: Arc<Mutex<HashMap<String, u32>>>,
     max_retries: u32,
+    queue: Option<Arc<dyn JobQueue>>,
+    /// Ids of queue-backed jobs that are queued, running or waiting on a retry, so a job
+    /// restored after a restart is not sent a second time when the caller resubmits it
+    queued_ids: Arc<std::sync::Mutex<HashSet<String>>>,
+    abort_on_first_failure: bool,
+    stopped: Arc<watch::Sender<bool>>,
+    paused: Arc<watch::Sender<bool>>,
+    browser_guard: Arc<BrowserGuard>,
+    dispatch_jitter_ms: Option<(u64, u64)>,
+    scheme_workers: HashMap<String, Arc<dyn JobWorker>>,
+    progress: Option<(Option<usize>, ProgressCallback)>,
+    /// Jobs accepted but not yet finished for good, including queued and pending retries
+    outstanding: Arc<watch::Sender<usize>>,
+    proxy_pool: Option<Arc<ProxyPool>>,
+    report: Arc<std::sync::Mutex<BatchReport>>,
+    metrics: Arc<dyn Metrics>,
+    dead_letters: Option<mpsc::Sender<DeadLetter>>,
+    save_failures: bool,
+}
+
+/// Snapshot of run progress passed to the progress callback
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    /// Jobs that finished for good, succeeded or failed without another retry
+    pub completed: usize,
+    /// Expected number of jobs, if known up front
+    pub total: Option<usize>,
+    /// Jobs currently executing or waiting on a permit
+    pub in_flight: usize,
+}
+
+type ProgressCallback = Arc<dyn Fn(Progress) + Send + Sync>;
+
+/// A job the healer gave up on, sent to the dead-letter channel for auditing or replay
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    pub job: Job,
+    /// The error from the final attempt
+    pub error: JobError,
+    /// Attempts made, including the final one
+    pub attempts: u32,
 }
 
 impl<S: Storage + 'static> Clone for Scheduler<S> {
@@ -21,12 +81,28 @@ impl<S: Storage + 'static> Clone for Scheduler<S> {
         Self {
             parser_worker: Arc::clone(&self.parser_worker),
             browser_worker: Arc::clone(&self.browser_worker),
+            firefox_worker: self.firefox_worker.clone(),
             storage: Arc::clone(&self.storage),
             sender: self.sender.clone(),
             concurrency_limit: Arc::clone(&self.concurrency_limit),
             error_healer: Arc::clone(&self.error_healer),
             retry_counts: Arc::clone(&self.retry_counts),
             max_retries: self.max_retries,
+            queue: self.queue.clone(),
+            queued_ids: Arc::clone(&self.queued_ids),
+            abort_on_first_failure: self.abort_on_first_failure,
+            stopped: Arc::clone(&self.stopped),
+            paused: Arc::clone(&self.paused),
+            browser_guard: Arc::clone(&self.browser_guard),
+            dispatch_jitter_ms: self.dispatch_jitter_ms,
+            scheme_workers: self.scheme_workers.clone(),
+            progress: self.progress.clone(),
+            outstanding: Arc::clone(&self.outstanding),
+            proxy_pool: self.proxy_pool.clone(),
+            report: Arc::clone(&self.report),
+            metrics: Arc::clone(&self.metrics),
+            dead_letters: self.dead_letters.clone(),
+            save_failures: self.save_failures,
         }
     }
 }
@@ -54,12 +130,28 @@ impl<S: Storage + 'static> Scheduler<S> {
         let scheduler = Self {
             parser_worker: Arc::new(parser),
             browser_worker: Arc::new(browser),
+            firefox_worker: None,
             storage: Arc::new(storage),
             sender: tx,
             concurrency_limit: Arc::new(Semaphore::new(max_concurrent)),
             error_healer: healer,
             retry_counts: Arc::new(Mutex::new(HashMap::new())),
             max_retries: 3,
+            queue: None,
+            queued_ids: Arc::new(std::sync::Mutex::new(HashSet::new())),
+            abort_on_first_failure: false,
+            stopped: Arc::new(watch::Sender::new(false)),
+            paused: Arc::new(watch::Sender::new(false)),
+            browser_guard: Arc::new(BrowserGuard::new(ResourceLimits::new())),
+            dispatch_jitter_ms: None,
+            scheme_workers: HashMap::new(),
+            progress: None,
+            outstanding: Arc::new(watch::Sender::new(0)),
+            proxy_pool: None,
+            report: Arc::new(std::sync::Mutex::new(BatchReport::default())),
+            metrics: Arc::new(NoopMetrics),
+            dead_letters: None,
+            save_failures: false,
         };
         (scheduler, rx)
     }
@@ -75,47 +167,407 @@ impl<S: Storage + 'static> Scheduler<S> {
         let scheduler = Self {
             parser_worker: Arc::clone(&worker),
             browser_worker: worker,
+            firefox_worker: None,
             storage: Arc::new(storage),
             sender: tx,
             concurrency_limit: Arc::new(Semaphore::new(max_concurrent)),
             error_healer: Arc::new(DefaultErrorHealer::new(3)),
             retry_counts: Arc::new(Mutex::new(HashMap::new())),
             max_retries: 3,
+            queue: None,
+            queued_ids: Arc::new(std::sync::Mutex::new(HashSet::new())),
+            abort_on_first_failure: false,
+            stopped: Arc::new(watch::Sender::new(false)),
+            paused: Arc::new(watch::Sender::new(false)),
+            browser_guard: Arc::new(BrowserGuard::new(ResourceLimits::new())),
+            dispatch_jitter_ms: None,
+            scheme_workers: HashMap::new(),
+            progress: None,
+            outstanding: Arc::new(watch::Sender::new(0)),
+            proxy_pool: None,
+            report: Arc::new(std::sync::Mutex::new(BatchReport::default())),
+            metrics: Arc::new(NoopMetrics),
+            dead_letters: None,
+            save_failures: false,
         };
         (scheduler, rx)
     }
 
-    pub fn submit(&self, job: Job) -> Result<(), mpsc::error::TrySendError<Job>> {
-        self.sender.try_send(job)
+    /// Persist submitted jobs to a durable queue so they can be restored after a crash
+    pub fn with_queue<Q: JobQueue + 'static>(mut self, queue: Q) -> Self {
+        self.queue = Some(Arc::new(queue));
+        self
+    }
+
+    /// Stop dispatching queued jobs as soon as any job fails for good
+    ///
+    /// Jobs already in flight are allowed to finish and their results are saved,
+    /// but anything still queued is dropped and `run` returns once they complete.
+    pub fn with_abort_on_first_failure(mut self) -> Self {
+        self.abort_on_first_failure = true;
+        self
+    }
+
+    /// Cap live browser jobs and pause launches while browser memory is over a threshold
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.browser_guard = Arc::new(BrowserGuard::new(limits));
+        self
+    }
+
+    /// Delay each dispatched job by a random time between `min_ms` and `max_ms`
+    /// before it starts, so bursts of jobs don't start in lockstep
+    ///
+    /// The delay counts against the concurrency limit like the job itself.
+    pub fn with_dispatch_jitter(mut self, min_ms: u64, max_ms: u64) -> Self {
+        self.dispatch_jitter_ms = Some((min_ms.min(max_ms), min_ms.max(max_ms)));
+        self
+    }
+
+    /// Route jobs whose URL uses `scheme` (e.g. `file`, `ftp`) to a custom worker
+    ///
+    /// `http` and `https` go to the parser or browser worker unless overridden here.
+    /// Jobs with any other unregistered scheme fail with a config error.
+    pub fn with_scheme_worker<W: JobWorker + 'static>(mut self, scheme: &str, worker: W) -> Self {
+        self.scheme_workers.insert(scheme.to_ascii_lowercase(), Arc::new(worker));
+        self
+    }
+
+    /// Run browser jobs whose `browser_config.browser_type` is `Firefox` on `worker`, such as
+    /// a `FirefoxWorker`; without one they go to the browser worker like any other
+    pub fn with_firefox_worker<W: JobWorker + 'static>(mut self, worker: W) -> Self {
+        self.firefox_worker = Some(Arc::new(worker));
+        self
+    }
+
+    /// Pick the worker for a job based on its URL scheme and browser type
+    fn worker_for(&self, job: &Job) -> Result<Arc<dyn JobWorker>, JobError> {
+        let scheme = job.url
+            .split_once("://")
+            .map_or("", |(scheme, _)| scheme)
+            .to_ascii_lowercase();
+
+        if let Some(worker) = self.scheme_workers.get(&scheme) {
+            return Ok(Arc::clone(worker));
+        }
+        match scheme.as_str() {
+            "http" | "https" if job.use_browser => {
+                let firefox = job.browser_config.as_ref().is_some_and(|c| c.browser_type == BrowserType::Firefox);
+                match &self.firefox_worker {
+                    Some(worker) if firefox => Ok(Arc::clone(worker)),
+                    _ => Ok(Arc::clone(&self.browser_worker)),
+                }
+            }
+            "http" | "https" => Ok(Arc::clone(&self.parser_worker)),
+            _ => Err(JobError::config_error(
+                "url",
+                format!("No worker registered for URL scheme '{}' in '{}'", scheme, job.url),
+            )),
+        }
+    }
+
+    /// Call `callback` each time a job finishes for good, e.g. to drive a progress bar
+    /// Pass the number of jobs you plan to submit as `total`, or `None` for open-ended runs
+    pub fn with_progress<F>(mut self, total: Option<usize>, callback: F) -> Self
+    where
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
+        self.progress = Some((total, Arc::new(callback)));
+        self
+    }
+
+    /// Report submissions, outcomes, retries and the in-flight count to `metrics`
+    pub fn with_metrics<M: Metrics + 'static>(mut self, metrics: M) -> Self {
+        self.metrics = Arc::new(metrics);
+        self
+    }
+
+    /// Also save a `JobResult` with `success: false` and the final error for each job that
+    /// fails for good, so storage keeps a record of failures alongside successes
+    pub fn with_saved_failures(mut self) -> Self {
+        self.save_failures = true;
+        self
+    }
+
+    /// Send every job the healer skips or aborts on to `sender`, with its final error
+    ///
+    /// Without one such jobs are only logged. The send waits for capacity, so a slow
+    /// consumer holds up the failed job's concurrency slot rather than losing the record.
+    pub fn with_dead_letters(mut self, sender: mpsc::Sender<DeadLetter>) -> Self {
+        self.dead_letters = Some(sender);
+        self
+    }
+
+    /// Assign each job without its own proxy one from `proxies`, round-robin
+    ///
+    /// When a job is rate limited or hits a CAPTCHA its proxy is marked blocked and any
+    /// retry goes out through a different one. Proxies blocked more often are handed out
+    /// only once the others have been blocked as many times.
+    pub fn with_proxy_pool(mut self, proxies: Vec<String>) -> Self {
+        self.proxy_pool = Some(Arc::new(ProxyPool::new(proxies)));
+        self
+    }
+
+    /// How many times each pooled proxy has been blocked
+    pub fn proxy_block_counts(&self) -> Vec<(String, u32)> {
+        self.proxy_pool.as_ref().map_or_else(Vec::new, |pool| pool.block_counts())
+    }
+
+    /// Whether the run loop has stopped dispatching, after `shutdown` or a fail-fast failure
+    pub fn is_stopped(&self) -> bool {
+        *self.stopped.borrow()
+    }
+
+    /// Stop dispatching queued jobs and cancel any pending delayed retries
+    /// In-flight jobs are allowed to finish before `run` returns
+    pub fn shutdown(&self) {
+        self.stopped.send_replace(true);
+    }
+
+    /// Stop taking queued jobs until `resume`, letting in-flight jobs finish
+    /// Queued jobs and pending retries stay buffered while paused
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    pub fn resume(&self) {
+        self.paused.send_replace(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Submit a job without waiting for capacity, failing with `TrySendError::Full` when the
+    /// channel is full
+    ///
+    /// The rejected job is handed back and it is up to the caller to resubmit it; prefer
+    /// `submit_async` when the caller can wait for capacity instead. With a queue, a job it
+    /// already has as done or failed is logged and not run again, and `Ok` is returned.
+    #[allow(clippy::result_large_err)] // hands the rejected job back to the caller
+    pub async fn submit(&self, job: Job) -> Result<(), mpsc::error::TrySendError<Job>> {
+        if !self.persist(&job).await {
+            return Ok(());
+        }
+        let job_id = job.id.clone();
+        self.reserve();
+        self.settle(&job_id, self.sender.try_send(job))
+    }
+
+    /// Submit a job, waiting for channel capacity instead of failing when full
+    ///
+    /// This is the backpressured counterpart of `submit`: a producer faster than the workers
+    /// is slowed down rather than losing jobs. Errors only when the run loop has gone away,
+    /// handing the job back. Jobs the queue has as finished are skipped, as for `submit`.
+    #[allow(clippy::result_large_err)] // hands the rejected job back to the caller
+    pub async fn submit_async(&self, job: Job) -> Result<(), mpsc::error::SendError<Job>> {
+        if !self.persist(&job).await {
+            return Ok(());
+        }
+        let job_id = job.id.clone();
+        self.reserve();
+        self.settle(&job_id, self.sender.send(job).await)
+    }
+
+    /// Submit every job from a stream, waiting for channel capacity instead of failing when full
+    ///
+    /// Returns the number of jobs sent once the stream ends, or the first job that could
+    /// not be sent because the run loop has gone away.
+    #[allow(clippy::result_large_err)] // hands the rejected job back to the caller
+    pub async fn submit_stream<St>(&self, jobs: St) -> Result<usize, mpsc::error::SendError<Job>>
+    where
+        St: futures::Stream<Item = Job>,
+    {
+        let mut jobs = std::pin::pin!(jobs);
+        let mut count = 0;
+        while let Some(job) = jobs.next().await {
+            if !self.persist(&job).await {
+                continue;
+            }
+            let job_id = job.id.clone();
+            self.reserve();
+            self.settle(&job_id, self.sender.send(job).await)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Count a job as outstanding before it is sent, so the run loop can't finish it
+    /// (and decrement the count) before it was ever counted
+    fn reserve(&self) {
+        self.outstanding.send_modify(|n| *n += 1);
+    }
+
+    /// Report a reserved job as submitted, or release its reservation if the send failed
+    fn settle<E>(&self, job_id: &str, sent: Result<(), E>) -> Result<(), E> {
+        match &sent {
+            Ok(()) => self.metrics.on_submit(),
+            Err(_) => {
+                self.outstanding.send_modify(|n| *n = n.saturating_sub(1));
+                self.queued_ids.lock().unwrap().remove(job_id);
+            }
+        }
+        sent
+    }
+
+    /// Record a job in the durable queue, if any
+    /// Returns false when the queue already has it as finished, or it is already queued or
+    /// running, and it should not be sent again
+    async fn persist(&self, job: &Job) -> bool {
+        if let Some(queue) = &self.queue {
+            if !self.queued_ids.lock().unwrap().insert(job.id.clone()) {
+                warn!(job_id = %job.id, "Job already queued or running, not submitting again");
+                return false;
+            }
+            match queue.status(&job.id).await {
+                Ok(Some(status)) if !status.is_unfinished() => {
+                    warn!(job_id = %job.id, ?status, "Job already finished, not submitting again");
+                    self.queued_ids.lock().unwrap().remove(&job.id);
+                    return false;
+                }
+                Ok(_) => {
+                    if let Err(e) = queue.enqueue(job).await {
+                        error!(job_id = %job.id, error = %e, "Failed to persist job");
+                    }
+                }
+                Err(e) => error!(job_id = %job.id, error = %e, "Failed to read queue status"),
+            }
+        }
+        true
+    }
+
+    /// Re-enqueue jobs left pending or in progress by a previous run, waiting for channel
+    /// capacity so any number of them can be restored
+    /// Returns the number of restored jobs; ones already submitted to this scheduler are skipped,
+    /// and submitting a restored job again does not queue a second copy
+    pub async fn restore(&self) -> anyhow::Result<usize> {
+        let Some(queue) = &self.queue else {
+            return Ok(0);
+        };
+        let mut count = 0;
+        for job in queue.unfinished().await? {
+            if !self.queued_ids.lock().unwrap().insert(job.id.clone()) {
+                info!(job_id = %job.id, "Job already queued or running, not restoring it");
+                continue;
+            }
+            info!(job_id = %job.id, "Restoring job from queue");
+            let job_id = job.id.clone();
+            self.reserve();
+            self.settle(&job_id, self.sender.send(job).await)
+                .map_err(|e| anyhow::anyhow!("Failed to restore job: {}", e))?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Wait until every submitted job has finished for good: nothing queued, in flight,
+    /// or waiting on a delayed retry
+    ///
+    /// Safe to cancel, e.g. inside `tokio::select!` or `tokio::time::timeout`.
+    /// Requires `run` to be executing, otherwise queued jobs never finish.
+    pub async fn wait_idle(&self) {
+        let mut outstanding = self.outstanding.subscribe();
+        let _ = outstanding.wait_for(|n| *n == 0).await;
+    }
+
+    /// Summary of every job that has finished for good so far
+    pub fn report(&self) -> BatchReport {
+        self.report.lock().unwrap().clone()
     }
 
+    /// Run `jobs` to completion, including retries, and report how they went
+    ///
+    /// The scheduler is shut down once everything has finished, so use `run` and
+    /// `submit` instead for a long-lived scheduler.
+    pub async fn run_batch(&self, receiver: mpsc::Receiver<Job>, jobs: Vec<Job>) -> BatchReport {
+        let feed = async {
+            if let Err(e) = self.submit_stream(futures::stream::iter(jobs)).await {
+                error!(job_id = %e.0.id, "Failed to submit job: run loop stopped");
+            }
+            self.wait_idle().await;
+            self.shutdown();
+        };
+        tokio::join!(self.run(receiver), feed);
+        self.report()
+    }
+
+    /// Dispatch jobs from `receiver` until it closes or the scheduler stops
+    ///
+    /// On `shutdown`, a healer returning `HealingAction::Abort`, or a fail-fast failure,
+    /// in-flight jobs are awaited (not cancelled) and their results saved; queued jobs and
+    /// pending delayed retries are dropped, then `run` returns.
     pub async fn run(&self, mut receiver: mpsc::Receiver<Job>) {
         let mut futures = FuturesUnordered::new();
+        let mut completed = 0;
+        let mut pending = PendingJobs::default();
 
         loop {
+            let mut stop_signal = self.stopped.subscribe();
+            let stopped = *stop_signal.borrow();
+            let mut pause_signal = self.paused.subscribe();
+            let paused = *pause_signal.borrow();
+            let jitter_ms = self.dispatch_jitter_ms
+                .map(|(min, max)| rand::thread_rng().gen_range(min..=max));
+            // Take the permit before choosing a job, so the choice sees everything queued meanwhile
+            let next_job = async {
+                let permit = Arc::clone(&self.concurrency_limit).acquire_owned().await.unwrap();
+                pending.fill(&mut receiver, self.sender.max_capacity());
+                pending.next(&mut receiver).await.map(|job| (job, permit))
+            };
             tokio::select! {
-                Some(job) = receiver.recv() => {
+                // Wake the loop when a shutdown is requested while idle
+                _ = stop_signal.changed(), if !stopped => {}
+                // Wake the loop on pause or resume
+                _ = pause_signal.changed(), if !stopped => {}
+                Some((mut job, permit)) = next_job, if !stopped && !paused => {
+                    if let Some(pool) = &self.proxy_pool
+                        && job.proxy.is_none()
+                    {
+                        job.proxy = pool.next(None);
+                    }
                     let storage = Arc::clone(&self.storage);
-                    let permit = Arc::clone(&self.concurrency_limit).acquire_owned().await.unwrap();
                     let error_healer = Arc::clone(&self.error_healer);
                     let retry_counts = Arc::clone(&self.retry_counts);
                     let max_retries = self.max_retries;
                     let sender = self.sender.clone();
+                    let queue = self.queue.clone();
+                    let abort_on_first_failure = self.abort_on_first_failure;
+                    let stopped_flag = Arc::clone(&self.stopped);
+                    let paused_flag = Arc::clone(&self.paused);
+                    let browser_guard = Arc::clone(&self.browser_guard);
+                    let outstanding = Arc::clone(&self.outstanding);
+                    let proxy_pool = self.proxy_pool.clone();
+                    let dead_letters = self.dead_letters.clone();
+                    let save_failures = self.save_failures;
 
-                    let worker = if job.use_browser {
-                        Arc::clone(&self.browser_worker)
-                    } else {
-                        Arc::clone(&self.parser_worker)
-                    };
+                    let worker = self.worker_for(&job);
 
                     futures.push(async move {
-                        let result = worker.execute(&job).await;
+                        // The delay holds the permit, so queued jobs stay in the channel meanwhile
+                        if let Some(ms) = jitter_ms {
+                            tokio::time::sleep(tokio::time::Duration::from_millis(ms)).await;
+                        }
+
+                        let browser_permit = if job.use_browser {
+                            browser_guard.acquire(&job.id).await
+                        } else {
+                            None
+                        };
+
+                        set_queue_status(&queue, &job.id, JobStatus::InProgress).await;
+                        let result = match worker {
+                            Ok(worker) => worker.execute(&job).await,
+                            Err(err) => Err(err),
+                        };
+                        drop(browser_permit);
                         
+                        // Cleared when the job is sent back for another attempt
+                        let mut finished = true;
                         match result {
                             Ok(ref r) => {
                                 let _ = storage.save_result(r).await;
                                 // Clear retry count on success
                                 retry_counts.lock().await.remove(&job.id);
+                                set_queue_status(&queue, &job.id, JobStatus::Done).await;
                             }
                             Err(ref err) => {
                                 // Get current retry count
@@ -134,46 +586,277 @@ impl<S: Storage + 'static> Scheduler<S> {
 
                                 // Ask healer what to do
                                 let action = error_healer.heal(&context).await;
+
+                                // A blocked proxy is reported and swapped out for any retry
+                                let mut retry_job = job.clone();
+                                if let (Some(pool), Some(proxy)) = (&proxy_pool, &job.proxy) {
+                                    let blocked = matches!(err.category, ErrorCategory::RateLimit | ErrorCategory::Captcha);
+                                    if blocked && pool.contains(proxy) {
+                                        pool.report_blocked(proxy);
+                                        retry_job.proxy = pool.next(Some(proxy));
+                                    }
+                                }
                                 
                                 match action {
                                     HealingAction::Retry => {
-                                        println!("Job {} failed (attempt {}), retrying immediately: {}", job.id, attempt, err);
-                                        let _ = sender.try_send(job.clone());
+                                        warn!(job_id = %job.id, attempt, max_attempts = max_retries, attempts_remaining = context.attempts_remaining(), error = %err, "Job failed, retrying immediately");
+                                        if !*stopped_flag.borrow() {
+                                            // Sent from its own task so this job's permit is released while
+                                            // it waits for channel capacity, or for resume when paused
+                                            finished = false;
+                                            let sender_clone = sender.clone();
+                                            tokio::spawn(async move {
+                                                if !requeue_when_resumed(retry_job, &sender_clone, &stopped_flag, &paused_flag).await {
+                                                    outstanding.send_modify(|n| *n = n.saturating_sub(1));
+                                                }
+                                            });
+                                        }
                                     }
                                     HealingAction::RetryAfter(ms) => {
-                                        println!("Job {} failed (attempt {}), retrying after {}ms: {}", job.id, attempt, ms, err);
-                                        let job_clone = job.clone();
+                                        warn!(job_id = %job.id, attempt, max_attempts = max_retries, attempts_remaining = context.attempts_remaining(), delay_ms = ms, error = %err, "Job failed, retrying after a delay");
+                                        finished = false;
+                                        let job_clone = retry_job;
                                         let sender_clone = sender.clone();
+                                        let mut stop_signal = stopped_flag.subscribe();
                                         tokio::spawn(async move {
-                                            tokio::time::sleep(tokio::time::Duration::from_millis(ms)).await;
-                                            let _ = sender_clone.try_send(job_clone);
+                                            // `wait_for` also sees a shutdown that came before this task started
+                                            tokio::select! {
+                                                _ = tokio::time::sleep(tokio::time::Duration::from_millis(ms)) => {}
+                                                _ = stop_signal.wait_for(|stopped| *stopped) => {}
+                                            }
+                                            let requeued = if *stop_signal.borrow() {
+                                                info!(job_id = %job_clone.id, "Scheduler stopped, cancelling pending retry");
+                                                false
+                                            } else {
+                                                info!(job_id = %job_clone.id, delay_ms = ms, attempt = attempt + 1, max_attempts = max_retries, "Retrying job");
+                                                requeue_when_resumed(job_clone, &sender_clone, &stopped_flag, &paused_flag).await
+                                            };
+                                            if !requeued {
+                                                outstanding.send_modify(|n| *n = n.saturating_sub(1));
+                                            }
                                         });
                                     }
                                     HealingAction::Skip => {
-                                        eprintln!("Job {} failed after {} attempts, skipping: {}", job.id, attempt, err);
+                                        error!(job_id = %job.id, attempt, max_attempts = max_retries, error = %err, "Job failed, skipping");
+                                        send_dead_letter(&dead_letters, &job, err, attempt).await;
+                                        set_queue_status(&queue, &job.id, JobStatus::Failed).await;
+                                        if abort_on_first_failure {
+                                            stopped_flag.send_replace(true);
+                                        }
                                     }
                                     HealingAction::Abort => {
-                                        eprintln!("Job {} failed, aborting workflow: {}", job.id, err);
-                                        // Could implement graceful shutdown here
+                                        error!(job_id = %job.id, attempt, max_attempts = max_retries, error = %err, "Job failed, aborting workflow");
+                                        send_dead_letter(&dead_letters, &job, err, attempt).await;
+                                        set_queue_status(&queue, &job.id, JobStatus::Failed).await;
+                                        // Same path as `shutdown`: in-flight jobs finish, nothing else is dispatched
+                                        stopped_flag.send_replace(true);
                                     }
                                 }
                             }
                         }
                         
+                        if finished && save_failures
+                            && let Err(err) = &result
+                        {
+                            let _ = storage.save_result(&JobResult::failed(&job, err.clone())).await;
+                        }
+
                         drop(permit);
-                        (job.id.clone(), result)
+                        (job.id.clone(), job.url.clone(), result, finished)
                     });
+                    self.metrics.on_in_flight(futures.len());
                 }
-                Some((job_id, res)) = futures.next() => {
-                    match res {
-                        Ok(_result) => println!("✓ Job {} succeeded", job_id),
-                        Err(err) => {
-                            eprintln!("✗ Job {} final error: {}", job_id, err);
+                Some((job_id, url, res, finished)) = futures.next() => {
+                    match &res {
+                        Ok(_result) => info!(job_id = %job_id, "Job succeeded"),
+                        Err(err) => error!(job_id = %job_id, error = %err, "Job failed"),
+                    }
+                    self.metrics.on_in_flight(futures.len());
+                    match (&res, finished) {
+                        (Ok(_), _) => self.metrics.on_success(),
+                        (Err(err), true) => self.metrics.on_failure(err),
+                        (Err(err), false) => self.metrics.on_retry(err),
+                    }
+                    if finished {
+                        self.queued_ids.lock().unwrap().remove(&job_id);
+                        self.report.lock().unwrap().record(&job_id, &url, &res);
+                        self.outstanding.send_modify(|n| *n = n.saturating_sub(1));
+                        completed += 1;
+                        if let Some((total, callback)) = &self.progress {
+                            callback(Progress { completed, total: *total, in_flight: futures.len() });
                         }
                     }
                 }
                 else => break,
             }
         }
+
+        if self.is_stopped() {
+            // Refuse further submissions, then discard what was already queued
+            receiver.close();
+            let mut dropped = pending.len();
+            self.outstanding.send_modify(|n| *n = n.saturating_sub(dropped));
+            while receiver.try_recv().is_ok() {
+                dropped += 1;
+                self.outstanding.send_modify(|n| *n = n.saturating_sub(1));
+            }
+            // Left unfinished in the queue, for `restore` in the next run
+            self.queued_ids.lock().unwrap().clear();
+            warn!(dropped, "Scheduler stopped, queued jobs not run");
+        }
+    }
+}
+
+/// Send a retry back to the run loop once the scheduler is not paused, waiting for channel capacity
+/// Returns false when it was dropped instead, because the scheduler stopped
+async fn requeue_when_resumed(
+    job: Job,
+    sender: &mpsc::Sender<Job>,
+    stopped: &watch::Sender<bool>,
+    paused: &watch::Sender<bool>,
+) -> bool {
+    let mut stop_signal = stopped.subscribe();
+    let mut pause_signal = paused.subscribe();
+    tokio::select! {
+        _ = pause_signal.wait_for(|paused| !*paused) => {}
+        _ = stop_signal.wait_for(|stopped| *stopped) => {}
+    }
+    if *stopped.borrow() {
+        info!(job_id = %job.id, "Scheduler stopped, cancelling pending retry");
+        return false;
+    }
+    sender.send(job).await.is_ok()
+}
+
+async fn send_dead_letter(dead_letters: &Option<mpsc::Sender<DeadLetter>>, job: &Job, error: &JobError, attempts: u32) {
+    if let Some(dead_letters) = dead_letters {
+        let letter = DeadLetter { job: job.clone(), error: error.clone(), attempts };
+        if dead_letters.send(letter).await.is_err() {
+            warn!(job_id = %job.id, "Dead-letter receiver dropped, job not recorded");
+        }
+    }
+}
+
+async fn set_queue_status(queue: &Option<Arc<dyn JobQueue>>, job_id: &str, status: JobStatus) {
+    if let Some(queue) = queue
+        && let Err(e) = queue.set_status(job_id, status).await
+    {
+        error!(job_id, ?status, error = %e, "Failed to update queue status");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use rocky_core::ErrorContext;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::time::{timeout, Duration};
+
+    /// Accepts every result without keeping it
+    struct NullStorage;
+
+    #[async_trait]
+    impl Storage for NullStorage {
+        async fn save_result(&self, _result: &JobResult) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Counts how many times it ran, failing every job when `fail` is set
+    #[derive(Clone, Default)]
+    struct CountingWorker {
+        runs: Arc<AtomicUsize>,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl JobWorker for CountingWorker {
+        async fn execute(&self, job: &Job) -> Result<JobResult, JobError> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            if self.fail {
+                return Err(JobError::fetch_error("connection reset"));
+            }
+            Ok(JobResult { job_id: job.id.clone(), success: true, output: serde_json::json!({}), tags: Vec::new(), error: None })
+        }
+    }
+
+    /// Retries after a long delay, reporting each failure it is asked about
+    struct DelayedRetryHealer {
+        healed: mpsc::UnboundedSender<u32>,
     }
-}
\ No newline at end of file
+
+    #[async_trait]
+    impl ErrorHealer for DelayedRetryHealer {
+        async fn heal(&self, context: &ErrorContext) -> HealingAction {
+            let _ = self.healed.send(context.attempt);
+            HealingAction::RetryAfter(60_000)
+        }
+    }
+
+    fn job(id: &str) -> Job {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "url": "https://example.com",
+            "use_browser": false,
+            "actions": [],
+            "browser_config": null,
+        })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn shutdown_cancels_a_pending_retry() {
+        let worker = CountingWorker { fail: true, ..Default::default() };
+        let (healed_tx, mut healed) = mpsc::unbounded_channel();
+        let healer = Arc::new(DelayedRetryHealer { healed: healed_tx });
+        let (scheduler, receiver) = Scheduler::with_healer(worker.clone(), worker.clone(), NullStorage, 10, 2, healer);
+
+        let run = scheduler.run(receiver);
+        let test = async {
+            scheduler.submit(job("job-1")).await.unwrap();
+            assert_eq!(healed.recv().await, Some(1));
+            // The retry is now waiting out its 60s delay
+            scheduler.shutdown();
+            timeout(Duration::from_secs(5), scheduler.wait_idle()).await
+                .expect("wait_idle should return once the pending retry is cancelled");
+        };
+        let ((), ()) = timeout(Duration::from_secs(10), async { tokio::join!(run, test) }).await
+            .expect("run should return after shutdown");
+
+        assert_eq!(worker.runs.load(Ordering::SeqCst), 1, "the job must not run again after shutdown");
+        assert!(healed.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn restored_job_runs_once_when_resubmitted_after_restart() {
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+        let dir = std::env::temp_dir().join(format!("rocky-restore-{}-{}", std::process::id(), nanos));
+        let dir = dir.to_str().unwrap().to_string();
+
+        // The first run accepts the job and stops before running it
+        let (crashed, _receiver) = Scheduler::with_single_worker(CountingWorker::default(), NullStorage, 10, 2);
+        let crashed = crashed.with_queue(rocky_storage::JsonFileQueue::new(&dir));
+        crashed.submit(job("job-1")).await.unwrap();
+        drop(crashed);
+
+        let worker = CountingWorker::default();
+        let queue = rocky_storage::JsonFileQueue::new(&dir);
+        let (scheduler, receiver) = Scheduler::with_single_worker(worker.clone(), NullStorage, 10, 2);
+        let scheduler = scheduler.with_queue(rocky_storage::JsonFileQueue::new(&dir));
+        assert_eq!(scheduler.restore().await.unwrap(), 1);
+        // The caller resubmits everything it had, not knowing what was restored
+        scheduler.submit(job("job-1")).await.unwrap();
+        assert_eq!(scheduler.restore().await.unwrap(), 0);
+
+        let feed = async {
+            timeout(Duration::from_secs(5), scheduler.wait_idle()).await.expect("job should finish");
+            scheduler.shutdown();
+        };
+        timeout(Duration::from_secs(10), async { tokio::join!(scheduler.run(receiver), feed) }).await
+            .expect("run should return after shutdown");
+
+        assert_eq!(worker.runs.load(Ordering::SeqCst), 1);
+        assert_eq!(queue.status("job-1").await.unwrap(), Some(JobStatus::Done));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,84 @@
+use rocky_core::JobError;
+
+/// Hooks the scheduler calls as jobs move through it, e.g. to back Prometheus counters
+///
+/// Every method does nothing by default, so implementations override only what they export.
+/// They are called from the run loop and must not block.
+pub trait Metrics: Send + Sync {
+    /// A job was accepted into the queue by `submit`, `submit_async`, `submit_stream` or `restore`
+    fn on_submit(&self) {}
+
+    /// A job succeeded, possibly after retries
+    fn on_success(&self) {}
+
+    /// A job failed for good, with no retry to follow
+    fn on_failure(&self, _error: &JobError) {}
+
+    /// A failed job is being sent back for another attempt
+    fn on_retry(&self, _error: &JobError) {}
+
+    /// The number of jobs executing or waiting on a concurrency permit changed
+    fn on_in_flight(&self, _count: usize) {}
+}
+
+/// The default `Metrics`, which records nothing
+pub struct NoopMetrics;
+
+impl Metrics for NoopMetrics {}
+
+/// Scheduler counters and the in-flight gauge as Prometheus metrics
+///
+/// Failures and retries are labelled with the error's category. Register with the registry
+/// your `/metrics` endpoint gathers from.
+#[cfg(feature = "prometheus")]
+pub struct PrometheusMetrics {
+    submitted: prometheus::IntCounter,
+    succeeded: prometheus::IntCounter,
+    failed: prometheus::IntCounterVec,
+    retried: prometheus::IntCounterVec,
+    in_flight: prometheus::IntGauge,
+}
+
+#[cfg(feature = "prometheus")]
+impl PrometheusMetrics {
+    pub fn new(registry: &prometheus::Registry) -> prometheus::Result<Self> {
+        use prometheus::{IntCounter, IntCounterVec, IntGauge, Opts};
+
+        let metrics = Self {
+            submitted: IntCounter::new("rocky_jobs_submitted_total", "Jobs accepted into the queue")?,
+            succeeded: IntCounter::new("rocky_jobs_succeeded_total", "Jobs that succeeded, possibly after retries")?,
+            failed: IntCounterVec::new(Opts::new("rocky_jobs_failed_total", "Jobs that failed with no retry to follow"), &["category"])?,
+            retried: IntCounterVec::new(Opts::new("rocky_jobs_retried_total", "Failed attempts sent back for a retry"), &["category"])?,
+            in_flight: IntGauge::new("rocky_jobs_in_flight", "Jobs executing or waiting on a concurrency permit")?,
+        };
+        registry.register(Box::new(metrics.submitted.clone()))?;
+        registry.register(Box::new(metrics.succeeded.clone()))?;
+        registry.register(Box::new(metrics.failed.clone()))?;
+        registry.register(Box::new(metrics.retried.clone()))?;
+        registry.register(Box::new(metrics.in_flight.clone()))?;
+        Ok(metrics)
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl Metrics for PrometheusMetrics {
+    fn on_submit(&self) {
+        self.submitted.inc();
+    }
+
+    fn on_success(&self) {
+        self.succeeded.inc();
+    }
+
+    fn on_failure(&self, error: &JobError) {
+        self.failed.with_label_values(&[format!("{:?}", error.category)]).inc();
+    }
+
+    fn on_retry(&self, error: &JobError) {
+        self.retried.with_label_values(&[format!("{:?}", error.category)]).inc();
+    }
+
+    fn on_in_flight(&self, count: usize) {
+        self.in_flight.set(count as i64);
+    }
+}
@@ -0,0 +1,66 @@
+use rocky_core::Job;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use tokio::sync::mpsc;
+
+/// Jobs pulled off the channel, handed out highest priority first and FIFO within a priority
+#[derive(Default)]
+pub(crate) struct PendingJobs {
+    heap: BinaryHeap<Pending>,
+    next_seq: u64,
+}
+
+struct Pending {
+    priority: u8,
+    seq: u64,
+    job: Job,
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority).then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PendingJobs {
+    pub(crate) fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Move already queued jobs off the channel, holding at most `limit` so backpressure still applies
+    pub(crate) fn fill(&mut self, receiver: &mut mpsc::Receiver<Job>, limit: usize) {
+        while self.heap.len() < limit {
+            let Ok(job) = receiver.try_recv() else {
+                break;
+            };
+            self.push(job);
+        }
+    }
+
+    fn push(&mut self, job: Job) {
+        self.heap.push(Pending { priority: job.priority, seq: self.next_seq, job });
+        self.next_seq += 1;
+    }
+
+    /// Highest priority pending job, or the next one to arrive on the channel if none are pending
+    pub(crate) async fn next(&mut self, receiver: &mut mpsc::Receiver<Job>) -> Option<Job> {
+        match self.heap.pop() {
+            Some(pending) => Some(pending.job),
+            None => receiver.recv().await,
+        }
+    }
+}
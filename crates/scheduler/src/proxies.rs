@@ -0,0 +1,50 @@
+use std::sync::Mutex;
+
+/// Proxies handed out to jobs round-robin, steering away from ones that get blocked
+pub(crate) struct ProxyPool {
+    proxies: Vec<String>,
+    state: Mutex<PoolState>,
+}
+
+struct PoolState {
+    cursor: usize,
+    /// Times each proxy was blocked (rate limited or served a CAPTCHA), by index
+    blocks: Vec<u32>,
+}
+
+impl ProxyPool {
+    pub(crate) fn new(proxies: Vec<String>) -> Self {
+        let blocks = vec![0; proxies.len()];
+        Self { proxies, state: Mutex::new(PoolState { cursor: 0, blocks }) }
+    }
+
+    pub(crate) fn contains(&self, proxy: &str) -> bool {
+        self.proxies.iter().any(|p| p == proxy)
+    }
+
+    /// Next proxy in rotation among those blocked least often, skipping `exclude` if possible
+    pub(crate) fn next(&self, exclude: Option<&str>) -> Option<String> {
+        if self.proxies.is_empty() {
+            return None;
+        }
+        let mut state = self.state.lock().unwrap();
+        let len = self.proxies.len();
+        let candidates = (0..len)
+            .map(|offset| (state.cursor + offset) % len)
+            .filter(|&i| len == 1 || Some(self.proxies[i].as_str()) != exclude);
+        let index = candidates.min_by_key(|&i| state.blocks[i])?;
+        state.cursor = (index + 1) % len;
+        Some(self.proxies[index].clone())
+    }
+
+    pub(crate) fn report_blocked(&self, proxy: &str) {
+        if let Some(index) = self.proxies.iter().position(|p| p == proxy) {
+            self.state.lock().unwrap().blocks[index] += 1;
+        }
+    }
+
+    pub(crate) fn block_counts(&self) -> Vec<(String, u32)> {
+        let state = self.state.lock().unwrap();
+        self.proxies.iter().cloned().zip(state.blocks.iter().copied()).collect()
+    }
+}
@@ -0,0 +1,61 @@
+use rocky_core::{ErrorCategory, JobError, JobResult};
+use std::collections::HashMap;
+
+/// Outcome of every job that finished for good, grouped for an at-a-glance health view
+#[derive(Debug, Clone, Default)]
+pub struct BatchReport {
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Failures per error category
+    pub by_category: HashMap<ErrorCategory, usize>,
+    /// Failures per URL host
+    pub by_host: HashMap<String, usize>,
+    /// Failed job ids with their final error, in the order they failed
+    pub failures: Vec<(String, JobError)>,
+}
+
+impl BatchReport {
+    pub(crate) fn record(&mut self, job_id: &str, url: &str, result: &Result<JobResult, JobError>) {
+        match result {
+            Ok(_) => self.succeeded += 1,
+            Err(err) => {
+                self.failed += 1;
+                *self.by_category.entry(err.category.clone()).or_default() += 1;
+                *self.by_host.entry(host(url)).or_default() += 1;
+                self.failures.push((job_id.to_string(), err.clone()));
+            }
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.succeeded + self.failed
+    }
+}
+
+impl std::fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} job(s): {} succeeded, {} failed", self.total(), self.succeeded, self.failed)?;
+
+        let mut categories: Vec<_> = self.by_category.iter().collect();
+        categories.sort_by(|a, b| b.1.cmp(a.1));
+        for (category, count) in categories {
+            writeln!(f, "  {:?}: {}", category, count)?;
+        }
+
+        let mut hosts: Vec<_> = self.by_host.iter().collect();
+        hosts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        for (host, count) in hosts {
+            writeln!(f, "  {}: {}", host, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Host part of a URL, without scheme, credentials, port or path
+/// IPv6 hosts keep their brackets; a URL that does not parse is counted as-is
+fn host(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(parsed) => parsed.host_str().unwrap_or(url).to_string(),
+        Err(_) => url.to_string(),
+    }
+}
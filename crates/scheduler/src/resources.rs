@@ -0,0 +1,113 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{info, warn};
+
+/// Process names used by Chromium-based browsers
+const BROWSER_PROCESS_NAMES: &[&str] = &["chrome", "chromium", "chromium-browse", "headless_shell"];
+
+/// Caps on browser usage to keep constrained hosts from running out of memory
+#[derive(Debug, Clone)]
+pub struct ResourceLimits {
+    /// Maximum number of browser jobs running at once
+    pub max_browsers: Option<usize>,
+    /// Pause launching browser jobs while browser processes use more than this (MB of RSS)
+    pub max_browser_memory_mb: Option<u64>,
+    /// How often to re-check memory while throttled
+    pub memory_check_interval: Duration,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResourceLimits {
+    pub fn new() -> Self {
+        Self {
+            max_browsers: None,
+            max_browser_memory_mb: None,
+            memory_check_interval: Duration::from_millis(1000),
+        }
+    }
+
+    pub fn with_max_browsers(mut self, max: usize) -> Self {
+        self.max_browsers = Some(max);
+        self
+    }
+
+    pub fn with_max_browser_memory_mb(mut self, mb: u64) -> Self {
+        self.max_browser_memory_mb = Some(mb);
+        self
+    }
+}
+
+/// Enforces `ResourceLimits` before a browser job is allowed to start
+pub(crate) struct BrowserGuard {
+    limits: ResourceLimits,
+    slots: Option<Arc<Semaphore>>,
+}
+
+impl BrowserGuard {
+    pub(crate) fn new(limits: ResourceLimits) -> Self {
+        let slots = limits.max_browsers.map(|n| Arc::new(Semaphore::new(n)));
+        Self { limits, slots }
+    }
+
+    /// Wait until a browser slot is free and memory is under the threshold
+    pub(crate) async fn acquire(&self, job_id: &str) -> Option<OwnedSemaphorePermit> {
+        let permit = match &self.slots {
+            Some(slots) => {
+                if slots.available_permits() == 0 {
+                    info!(job_id, max_browsers = self.limits.max_browsers.unwrap_or(0), "Waiting for a browser slot");
+                }
+                Some(Arc::clone(slots).acquire_owned().await.unwrap())
+            }
+            None => None,
+        };
+
+        if let Some(max_mb) = self.limits.max_browser_memory_mb {
+            let mut logged = false;
+            while let Some(used_mb) = browser_memory_mb() {
+                if used_mb <= max_mb {
+                    break;
+                }
+                if !logged {
+                    warn!(job_id, used_mb, max_mb, "Throttling browser job over the memory limit");
+                    logged = true;
+                }
+                tokio::time::sleep(self.limits.memory_check_interval).await;
+            }
+        }
+
+        permit
+    }
+}
+
+/// Total resident memory of running browser processes in MB
+/// Returns None where /proc is unavailable (non-Linux hosts)
+pub fn browser_memory_mb() -> Option<u64> {
+    let entries = std::fs::read_dir("/proc").ok()?;
+    let mut total_kb = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(comm) = std::fs::read_to_string(path.join("comm")) else {
+            continue;
+        };
+        if !BROWSER_PROCESS_NAMES.contains(&comm.trim()) {
+            continue;
+        }
+        let Ok(status) = std::fs::read_to_string(path.join("status")) else {
+            continue;
+        };
+        if let Some(line) = status.lines().find(|l| l.starts_with("VmRSS:")) {
+            total_kb += line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(0);
+        }
+    }
+    Some(total_kb / 1024)
+}
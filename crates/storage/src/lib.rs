@@ -1,30 +1,758 @@
 use async_trait::async_trait;
-use rocky_core::JobResult;
+use rocky_core::{ErrorCategory, Job, JobError, JobResult, RecordSink};
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::path::Path;
 use anyhow::Result;
 
 #[async_trait]
 pub trait Storage: Send + Sync {
     async fn save_result(&self, result: &JobResult) -> Result<()>;
+
+    /// Load the last stored result for a job, if the backend supports reading back
+    async fn load_result(&self, _job_id: &str) -> Result<Option<JobResult>> {
+        Ok(None)
+    }
+
+    /// Append records streamed out of a job, if the backend supports incremental writes
+    async fn append_records(&self, _job_id: &str, _key: &str, _records: &[serde_json::Value]) -> Result<()> {
+        Err(anyhow::anyhow!("This storage does not support appending records"))
+    }
+
+    /// All stored results carrying `tag`, if the backend supports listing
+    async fn list_by_tag(&self, _tag: &str) -> Result<Vec<JobResult>> {
+        Err(anyhow::anyhow!("This storage does not support listing by tag"))
+    }
+}
+
+/// Adapts a `Storage` into a `RecordSink` so workers can stream records into it
+pub struct StorageSink<S: Storage> {
+    storage: Arc<S>,
+}
+
+impl<S: Storage> StorageSink<S> {
+    pub fn new(storage: Arc<S>) -> Self {
+        Self { storage }
+    }
+}
+
+#[async_trait]
+impl<S: Storage> RecordSink for StorageSink<S> {
+    async fn write_records(&self, job_id: &str, key: &str, records: &[serde_json::Value]) -> std::result::Result<(), JobError> {
+        self.storage
+            .append_records(job_id, key, records)
+            .await
+            .map_err(|e| JobError::new(ErrorCategory::Unknown, format!("Failed to write records: {}", e)))
+    }
+}
+
+/// On-disk encoding for stored results
+///
+/// JSON is the default and stays human-readable; the binary formats are smaller and
+/// faster to load for large result sets and need the `msgpack` or `cbor` feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Json,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+    #[cfg(feature = "cbor")]
+    Cbor,
 }
 
+impl Format {
+    /// File extension for results written in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => "msgpack",
+            #[cfg(feature = "cbor")]
+            Format::Cbor => "cbor",
+        }
+    }
+
+    pub fn encode(&self, result: &JobResult) -> Result<Vec<u8>> {
+        match self {
+            Format::Json => Ok(serde_json::to_string_pretty(&canonical_json(&serde_json::to_value(result)?))?.into_bytes()),
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => Ok(rmp_serde::to_vec_named(result)?),
+            #[cfg(feature = "cbor")]
+            Format::Cbor => {
+                let mut data = Vec::new();
+                ciborium::into_writer(result, &mut data)?;
+                Ok(data)
+            }
+        }
+    }
+
+    pub fn decode(&self, data: &[u8]) -> Result<JobResult> {
+        match self {
+            Format::Json => Ok(serde_json::from_slice(data)?),
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => Ok(rmp_serde::from_slice(data)?),
+            #[cfg(feature = "cbor")]
+            Format::Cbor => Ok(ciborium::from_reader(data)?),
+        }
+    }
+}
+
+/// Stores one file per result, `{job_id}.json` by default
 pub struct JsonFileStorage {
     pub folder: String,
+    pub format: Format,
 }
 
 impl JsonFileStorage {
     pub fn new(folder: &str) -> Self {
+        Self::with_format(folder, Format::Json)
+    }
+
+    /// Store results in `format` instead of JSON, as `{job_id}.{extension}`
+    pub fn with_format(folder: &str, format: Format) -> Self {
         std::fs::create_dir_all(folder).ok(); // ensure folder exists
-        Self { folder: folder.to_string() }
+        Self { folder: folder.to_string(), format }
+    }
+
+    fn result_path(&self, job_id: &str) -> std::path::PathBuf {
+        Path::new(&self.folder).join(format!("{}.{}", job_id, self.format.extension()))
     }
 }
 
 #[async_trait]
 impl Storage for JsonFileStorage {
     async fn save_result(&self, result: &JobResult) -> Result<()> {
-        let path = Path::new(&self.folder).join(format!("{}.json", result.job_id));
-        let data = serde_json::to_string_pretty(result)?;
-        tokio::fs::write(path, data).await?;
+        let data = self.format.encode(result)?;
+        tokio::fs::write(self.result_path(&result.job_id), data).await?;
+        Ok(())
+    }
+
+    /// Appends one `{"key", "record"}` JSON line per record to `{job_id}.records.ndjson`
+    async fn append_records(&self, job_id: &str, key: &str, records: &[serde_json::Value]) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let path = Path::new(&self.folder).join(format!("{}.records.ndjson", job_id));
+        let mut data = String::new();
+        for record in records {
+            data.push_str(&serde_json::json!({ "key": key, "record": canonical_json(record) }).to_string());
+            data.push('\n');
+        }
+        let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+        file.write_all(data.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn load_result(&self, job_id: &str) -> Result<Option<JobResult>> {
+        let path = self.result_path(job_id);
+        if !tokio::fs::try_exists(&path).await? {
+            return Ok(None);
+        }
+        let data = tokio::fs::read(path).await?;
+        Ok(Some(self.format.decode(&data)?))
+    }
+
+    /// Scans every result file in the folder; files that aren't results are skipped
+    async fn list_by_tag(&self, tag: &str) -> Result<Vec<JobResult>> {
+        let mut results = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.folder).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(self.format.extension()) {
+                continue;
+            }
+            let data = tokio::fs::read(&path).await?;
+            if let Ok(result) = self.format.decode(&data)
+                && result.tags.iter().any(|t| t == tag)
+            {
+                results.push(result);
+            }
+        }
+        results.sort_by(|a, b| a.job_id.cmp(&b.job_id));
+        Ok(results)
+    }
+}
+
+/// Appends every result as one JSON line to a single file, written as soon as it arrives
+///
+/// Meant for long crawls: nothing is held in memory and the file can be tailed or
+/// resumed from. `append_page` records a crawled page with its URL and depth;
+/// `write_summary` closes the log with the number of pages visited.
+///
+/// Safe to share between concurrent jobs: lines are written whole, one writer at a time,
+/// and flushed once per write, so a batch from `append_records` costs a single flush.
+pub struct NdjsonStorage {
+    path: std::path::PathBuf,
+    writer: tokio::sync::Mutex<Option<LineWriter>>,
+    visited: std::sync::atomic::AtomicUsize,
+    max_bytes: Option<u64>,
+}
+
+/// Same backend under the name data pipelines usually look for
+pub type JsonLinesStorage = NdjsonStorage;
+
+/// The open file lines go to, and how far it has grown
+struct LineWriter {
+    file: tokio::io::BufWriter<tokio::fs::File>,
+    bytes: u64,
+    /// Rotation index, 0 for the configured path itself
+    index: u32,
+}
+
+impl NdjsonStorage {
+    pub fn new(path: &str) -> Self {
+        if let Some(parent) = Path::new(path).parent() {
+            std::fs::create_dir_all(parent).ok(); // ensure folder exists
+        }
+        Self {
+            path: path.into(),
+            writer: tokio::sync::Mutex::new(None),
+            visited: std::sync::atomic::AtomicUsize::new(0),
+            max_bytes: None,
+        }
+    }
+
+    /// Roll over to `{stem}.1.{ext}`, `{stem}.2.{ext}`, ... once a file would pass `max_bytes`
+    /// A single line larger than `max_bytes` still gets a file of its own
+    pub fn with_rotation(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    fn file_path(&self, index: u32) -> std::path::PathBuf {
+        if index == 0 {
+            return self.path.clone();
+        }
+        let stem = self.path.file_stem().and_then(|s| s.to_str()).unwrap_or("results");
+        let name = match self.path.extension().and_then(|e| e.to_str()) {
+            Some(ext) => format!("{}.{}.{}", stem, index, ext),
+            None => format!("{}.{}", stem, index),
+        };
+        self.path.with_file_name(name)
+    }
+
+    /// Highest rotation index already on disk, so a reopened log carries on from its newest file
+    async fn last_index(&self) -> Result<u32> {
+        if self.max_bytes.is_none() {
+            return Ok(0);
+        }
+        let dir = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut entries = match tokio::fs::read_dir(dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let stem = self.path.file_stem().and_then(|s| s.to_str()).unwrap_or("results");
+        let ext = self.path.extension().and_then(|e| e.to_str());
+        let mut last = 0;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let Some(rest) = name.to_str().and_then(|n| n.strip_prefix(stem)).and_then(|n| n.strip_prefix('.')) else {
+                continue;
+            };
+            let index = match ext {
+                Some(ext) => rest.strip_suffix(ext).and_then(|r| r.strip_suffix('.')),
+                None => Some(rest),
+            };
+            if let Some(index) = index.and_then(|i| i.parse::<u32>().ok()) {
+                last = last.max(index);
+            }
+        }
+        Ok(last)
+    }
+
+    async fn open(&self, index: u32) -> Result<LineWriter> {
+        let path = self.file_path(index);
+        let file = tokio::fs::OpenOptions::new().create(true).append(true).open(&path).await?;
+        let bytes = file.metadata().await?.len();
+        Ok(LineWriter { file: tokio::io::BufWriter::new(file), bytes, index })
+    }
+
+    async fn append_line(&self, line: &serde_json::Value) -> Result<()> {
+        self.append_lines(std::slice::from_ref(line)).await
+    }
+
+    /// Write `lines` in order and flush once they are all written, or when a file is rotated
+    async fn append_lines(&self, lines: &[serde_json::Value]) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        // One writer at a time so concurrent jobs never interleave lines
+        let mut guard = self.writer.lock().await;
+        let mut writer = match guard.take() {
+            Some(writer) => writer,
+            None => self.open(self.last_index().await?).await?,
+        };
+        for line in lines {
+            let mut data = canonical_json(line).to_string();
+            data.push('\n');
+            if let Some(max_bytes) = self.max_bytes
+                && writer.bytes > 0 && writer.bytes + data.len() as u64 > max_bytes
+            {
+                writer.file.flush().await?;
+                writer = self.open(writer.index + 1).await?;
+            }
+            writer.file.write_all(data.as_bytes()).await?;
+            writer.bytes += data.len() as u64;
+        }
+        writer.file.flush().await?;
+        *guard = Some(writer);
+        Ok(())
+    }
+
+    /// Append a crawled page as `{url, depth, output, success}`
+    pub async fn append_page(&self, url: &str, depth: u32, result: &JobResult) -> Result<()> {
+        self.append_line(&serde_json::json!({
+            "url": url,
+            "depth": depth,
+            "job_id": result.job_id,
+            "success": result.success,
+            "output": result.output,
+        })).await?;
+        self.visited.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Number of lines written so far
+    pub fn visited(&self) -> usize {
+        self.visited.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Append a final `{"summary": {"visited": n}}` line and return the count
+    pub async fn write_summary(&self) -> Result<usize> {
+        let visited = self.visited();
+        self.append_line(&serde_json::json!({ "summary": { "visited": visited } })).await?;
+        Ok(visited)
+    }
+}
+
+#[async_trait]
+impl Storage for NdjsonStorage {
+    /// Appends `{job_id, success, output, tags}`; use `append_page` when the URL and depth are known
+    async fn save_result(&self, result: &JobResult) -> Result<()> {
+        self.append_line(&serde_json::to_value(result)?).await?;
+        self.visited.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Appends one `{job_id, key, record}` line per record
+    async fn append_records(&self, job_id: &str, key: &str, records: &[serde_json::Value]) -> Result<()> {
+        let lines: Vec<_> = records.iter()
+            .map(|record| serde_json::json!({ "job_id": job_id, "key": key, "record": record }))
+            .collect();
+        self.append_lines(&lines).await
+    }
+}
+
+/// Copy of `value` with object keys sorted recursively
+/// Keeps serialized output byte-stable even if serde_json's `preserve_order` feature
+/// gets enabled somewhere in the dependency graph
+pub fn canonical_json(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(obj) => {
+            let mut keys: Vec<&String> = obj.keys().collect();
+            keys.sort();
+            let mut sorted = serde_json::Map::new();
+            for key in keys {
+                sorted.insert(key.clone(), canonical_json(&obj[key]));
+            }
+            serde_json::Value::Object(sorted)
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.iter().map(canonical_json).collect())
+        }
+        other => other.clone(),
+    }
+}
+
+/// Hash of a result's content (success flag and output), ignoring the job id
+pub fn content_hash(result: &JobResult) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    result.success.hash(&mut hasher);
+    canonical_json(&result.output).to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Wraps any storage and skips writes when a result is unchanged since the last save
+pub struct DedupStorage<S: Storage> {
+    inner: S,
+}
+
+impl<S: Storage> DedupStorage<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl<S: Storage> Storage for DedupStorage<S> {
+    async fn save_result(&self, result: &JobResult) -> Result<()> {
+        if let Some(previous) = self.inner.load_result(&result.job_id).await?
+            && content_hash(&previous) == content_hash(result)
+        {
+            tracing::debug!(job_id = %result.job_id, "Result unchanged, skipping write");
+            return Ok(());
+        }
+        self.inner.save_result(result).await
+    }
+
+    async fn load_result(&self, job_id: &str) -> Result<Option<JobResult>> {
+        self.inner.load_result(job_id).await
+    }
+
+    async fn append_records(&self, job_id: &str, key: &str, records: &[serde_json::Value]) -> Result<()> {
+        self.inner.append_records(job_id, key, records).await
+    }
+
+    async fn list_by_tag(&self, tag: &str) -> Result<Vec<JobResult>> {
+        self.inner.list_by_tag(tag).await
+    }
+}
+
+/// Holds results in memory and writes them to the inner storage once a size cap is hit
+///
+/// Results are evicted oldest first, so memory stays under `max_retained_bytes` however
+/// fast jobs finish. Call `flush` at the end of a run to persist whatever is still held;
+/// dropping it with results still held logs a warning and loses them.
+pub struct BufferedStorage<S: Storage> {
+    inner: S,
+    max_retained_bytes: usize,
+    buffer: tokio::sync::Mutex<RetainedResults>,
+}
+
+#[derive(Default)]
+struct RetainedResults {
+    results: std::collections::VecDeque<(JobResult, usize)>,
+    bytes: usize,
+}
+
+impl<S: Storage> BufferedStorage<S> {
+    pub fn new(inner: S, max_retained_bytes: usize) -> Self {
+        Self { inner, max_retained_bytes, buffer: tokio::sync::Mutex::new(RetainedResults::default()) }
+    }
+
+    /// Approximate size of the results currently held in memory, in bytes of JSON
+    pub async fn retained_bytes(&self) -> usize {
+        self.buffer.lock().await.bytes
+    }
+
+    /// Number of results currently held in memory
+    pub async fn retained_count(&self) -> usize {
+        self.buffer.lock().await.results.len()
+    }
+
+    /// Write every retained result to the inner storage
+    pub async fn flush(&self) -> Result<()> {
+        let mut buffer = self.buffer.lock().await;
+        while let Some((result, size)) = buffer.results.pop_front() {
+            if let Err(e) = self.inner.save_result(&result).await {
+                buffer.results.push_front((result, size));
+                return Err(e);
+            }
+            buffer.bytes -= size;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S: Storage> Storage for BufferedStorage<S> {
+    async fn save_result(&self, result: &JobResult) -> Result<()> {
+        let size = serde_json::to_vec(result)?.len();
+        let mut buffer = self.buffer.lock().await;
+        buffer.results.push_back((result.clone(), size));
+        buffer.bytes += size;
+
+        while buffer.bytes > self.max_retained_bytes {
+            let Some((oldest, oldest_size)) = buffer.results.pop_front() else {
+                break;
+            };
+            if let Err(e) = self.inner.save_result(&oldest).await {
+                buffer.results.push_front((oldest, oldest_size));
+                return Err(e);
+            }
+            buffer.bytes -= oldest_size;
+        }
+        Ok(())
+    }
+
+    async fn load_result(&self, job_id: &str) -> Result<Option<JobResult>> {
+        let retained = self.buffer.lock().await.results
+            .iter()
+            .rev()
+            .find(|(r, _)| r.job_id == job_id)
+            .map(|(r, _)| r.clone());
+        match retained {
+            Some(result) => Ok(Some(result)),
+            None => self.inner.load_result(job_id).await,
+        }
+    }
+
+    async fn append_records(&self, job_id: &str, key: &str, records: &[serde_json::Value]) -> Result<()> {
+        self.inner.append_records(job_id, key, records).await
+    }
+
+    /// Retained results replace any older copy the inner storage has for the same job
+    async fn list_by_tag(&self, tag: &str) -> Result<Vec<JobResult>> {
+        let buffer = self.buffer.lock().await;
+        let mut results: Vec<JobResult> = self.inner.list_by_tag(tag).await?
+            .into_iter()
+            .filter(|r| !buffer.results.iter().any(|(retained, _)| retained.job_id == r.job_id))
+            .collect();
+        // Newest first, so a job saved twice is listed by its latest result only
+        let mut seen = std::collections::HashSet::new();
+        for (retained, _) in buffer.results.iter().rev() {
+            if seen.insert(retained.job_id.as_str()) && retained.tags.iter().any(|t| t == tag) {
+                results.push(retained.clone());
+            }
+        }
+        Ok(results)
+    }
+}
+
+impl<S: Storage> Drop for BufferedStorage<S> {
+    fn drop(&mut self) {
+        // Writing needs an await, so all a drop can do is say what is about to be lost
+        let buffer = self.buffer.get_mut();
+        if !buffer.results.is_empty() {
+            tracing::warn!(
+                results = buffer.results.len(),
+                bytes = buffer.bytes,
+                "BufferedStorage dropped with unflushed results; call flush before dropping it"
+            );
+        }
+    }
+}
+
+/// A message bus client (Kafka, NATS, ...) that `StreamStorage` publishes through
+#[async_trait]
+pub trait Publisher: Send + Sync {
+    async fn publish(&self, topic: &str, key: &str, payload: Vec<u8>) -> Result<()>;
+}
+
+/// Publishes each result as JSON to a topic, keyed by job id
+pub struct StreamStorage<P: Publisher> {
+    publisher: P,
+    topic: String,
+    in_flight: tokio::sync::Semaphore,
+}
+
+impl<P: Publisher> StreamStorage<P> {
+    pub fn new(publisher: P, topic: &str) -> Self {
+        Self::with_max_in_flight(publisher, topic, 64)
+    }
+
+    /// Limit concurrent publishes so a slow broker applies backpressure to the scheduler
+    pub fn with_max_in_flight(publisher: P, topic: &str, max_in_flight: usize) -> Self {
+        Self {
+            publisher,
+            topic: topic.to_string(),
+            in_flight: tokio::sync::Semaphore::new(max_in_flight),
+        }
+    }
+}
+
+#[async_trait]
+impl<P: Publisher> Storage for StreamStorage<P> {
+    async fn save_result(&self, result: &JobResult) -> Result<()> {
+        let _permit = self.in_flight.acquire().await?;
+        let payload = serde_json::to_vec(&canonical_json(&serde_json::to_value(result)?))?;
+        self.publisher
+            .publish(&self.topic, &result.job_id, payload)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to publish result for job {} to {}: {}", result.job_id, self.topic, e))
+    }
+}
+
+/// Publishes to a NATS subject, sending the job id as the `Nats-Msg-Id` header
+/// so JetStream can deduplicate repeated results
+#[cfg(feature = "nats")]
+pub struct NatsPublisher {
+    client: async_nats::Client,
+}
+
+#[cfg(feature = "nats")]
+impl NatsPublisher {
+    pub fn new(client: async_nats::Client) -> Self {
+        Self { client }
+    }
+
+    pub async fn connect(url: &str) -> Result<Self> {
+        Ok(Self { client: async_nats::connect(url).await? })
+    }
+}
+
+#[cfg(feature = "nats")]
+#[async_trait]
+impl Publisher for NatsPublisher {
+    async fn publish(&self, topic: &str, key: &str, payload: Vec<u8>) -> Result<()> {
+        let mut headers = async_nats::HeaderMap::new();
+        headers.insert("Nats-Msg-Id", key);
+        self.client
+            .publish_with_headers(topic.to_string(), headers, payload.into())
+            .await?;
+        Ok(())
+    }
+}
+
+/// Lifecycle state of a job tracked by a `JobQueue`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum JobStatus {
+    Pending,
+    InProgress,
+    Done,
+    Failed,
+}
+
+impl JobStatus {
+    /// Whether the job still needs to run after a restart
+    pub fn is_unfinished(&self) -> bool {
+        matches!(self, JobStatus::Pending | JobStatus::InProgress)
+    }
+}
+
+/// Durable record of submitted jobs, used to recover the queue after a crash
+#[async_trait]
+pub trait JobQueue: Send + Sync {
+    async fn enqueue(&self, job: &Job) -> Result<()>;
+    async fn set_status(&self, job_id: &str, status: JobStatus) -> Result<()>;
+    async fn status(&self, job_id: &str) -> Result<Option<JobStatus>>;
+    /// Jobs that were pending or in progress when last recorded
+    async fn unfinished(&self) -> Result<Vec<Job>>;
+}
+
+#[derive(Serialize, Deserialize)]
+struct QueueEntry {
+    status: JobStatus,
+    job: Job,
+}
+
+/// Stores one JSON file per job holding the job and its status
+pub struct JsonFileQueue {
+    pub folder: String,
+}
+
+impl JsonFileQueue {
+    pub fn new(folder: &str) -> Self {
+        std::fs::create_dir_all(folder).ok(); // ensure folder exists
+        Self { folder: folder.to_string() }
+    }
+
+    fn entry_path(&self, job_id: &str) -> std::path::PathBuf {
+        Path::new(&self.folder).join(format!("{}.json", job_id))
+    }
+
+    async fn read_entry(&self, job_id: &str) -> Result<Option<QueueEntry>> {
+        let path = self.entry_path(job_id);
+        if !tokio::fs::try_exists(&path).await? {
+            return Ok(None);
+        }
+        let data = tokio::fs::read(path).await?;
+        Ok(Some(serde_json::from_slice(&data)?))
+    }
+
+    /// Write to a temp file and rename it over the entry, so a crash mid-write never
+    /// leaves a truncated entry behind
+    async fn write_entry(&self, entry: &QueueEntry) -> Result<()> {
+        let data = serde_json::to_vec_pretty(entry)?;
+        let path = self.entry_path(&entry.job.id);
+        let temp = path.with_extension("json.tmp");
+        tokio::fs::write(&temp, data).await?;
+        tokio::fs::rename(&temp, &path).await?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[async_trait]
+impl JobQueue for JsonFileQueue {
+    async fn enqueue(&self, job: &Job) -> Result<()> {
+        self.write_entry(&QueueEntry { status: JobStatus::Pending, job: job.clone() }).await
+    }
+
+    async fn set_status(&self, job_id: &str, status: JobStatus) -> Result<()> {
+        match self.read_entry(job_id).await? {
+            Some(mut entry) => {
+                entry.status = status;
+                self.write_entry(&entry).await
+            }
+            None => Err(anyhow::anyhow!("Job {} is not in the queue", job_id)),
+        }
+    }
+
+    async fn status(&self, job_id: &str) -> Result<Option<JobStatus>> {
+        Ok(self.read_entry(job_id).await?.map(|e| e.status))
+    }
+
+    /// Entries that can't be read or parsed are logged and skipped, so one corrupted
+    /// file doesn't stop the rest from being restored
+    async fn unfinished(&self) -> Result<Vec<Job>> {
+        let mut jobs = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.folder).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let entry = match tokio::fs::read(&path).await {
+                Ok(data) => serde_json::from_slice::<QueueEntry>(&data).map_err(anyhow::Error::from),
+                Err(e) => Err(e.into()),
+            };
+            match entry {
+                Ok(entry) if entry.status.is_unfinished() => jobs.push(entry.job),
+                Ok(_) => {}
+                Err(e) => tracing::warn!(path = %path.display(), error = %e, "Skipping unreadable queue entry"),
+            }
+        }
+        Ok(jobs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    /// The same record written by two scrapers that emit keys in different orders
+    const FORWARD: &str = r#"{"title":"Dune","price":{"currency":"EUR","amount":10},"tags":[{"z":1,"a":2}]}"#;
+    const BACKWARD: &str = r#"{"tags":[{"a":2,"z":1}],"price":{"amount":10,"currency":"EUR"},"title":"Dune"}"#;
+    const CANONICAL: &str = r#"{"price":{"amount":10,"currency":"EUR"},"tags":[{"a":2,"z":1}],"title":"Dune"}"#;
+
+    fn parse(raw: &str) -> Value {
+        serde_json::from_str(raw).unwrap()
+    }
+
+    fn result(job_id: &str, output: Value) -> JobResult {
+        JobResult { job_id: job_id.to_string(), success: true, output, tags: Vec::new(), error: None }
+    }
+
+    #[test]
+    fn canonical_json_sorts_keys_at_every_level() {
+        assert_ne!(FORWARD, BACKWARD);
+        assert_eq!(canonical_json(&parse(FORWARD)).to_string(), CANONICAL);
+        assert_eq!(canonical_json(&parse(BACKWARD)).to_string(), CANONICAL);
+    }
+
+    #[test]
+    fn content_hash_is_the_hash_of_the_canonical_output() {
+        let mut hasher = DefaultHasher::new();
+        true.hash(&mut hasher);
+        CANONICAL.hash(&mut hasher);
+        let expected = hasher.finish();
+
+        assert_eq!(content_hash(&result("job-1", parse(FORWARD))), expected);
+        assert_eq!(content_hash(&result("job-2", parse(BACKWARD))), expected);
+    }
+
+    #[test]
+    fn content_hash_differs_when_content_changes() {
+        let original = result("job-1", json!({ "price": 10 }));
+        assert_ne!(content_hash(&original), content_hash(&result("job-1", json!({ "price": 11 }))));
+
+        let mut failed = original.clone();
+        failed.success = false;
+        assert_ne!(content_hash(&original), content_hash(&failed));
+    }
+}